@@ -0,0 +1,36 @@
+use std::env;
+
+fn main() {
+    if let Ok(version) = env::var("DEP_OPENSSL_VERSION_NUMBER") {
+        let version = u64::from_str_radix(&version, 16).unwrap();
+
+        if version >= 0x1010_0000 {
+            println!("cargo:rustc-cfg=have_min_max_version");
+            println!("cargo:rustc-cfg=have_ossl110");
+        }
+
+        if version >= 0x1010_0080 {
+            println!("cargo:rustc-cfg=have_ossl110h");
+        }
+
+        if version >= 0x1010_1000 {
+            println!("cargo:rustc-cfg=have_ossl111");
+        }
+
+        if version >= 0x3000_0000 {
+            println!("cargo:rustc-cfg=have_ossl300");
+        }
+
+        if version >= 0x3020_0000 {
+            println!("cargo:rustc-cfg=have_ossl320");
+        }
+    }
+
+    if let Ok(version) = env::var("DEP_OPENSSL_LIBRESSL_VERSION_NUMBER") {
+        let version = u64::from_str_radix(&version, 16).unwrap();
+
+        if version >= 0x2060_1000 {
+            println!("cargo:rustc-cfg=have_min_max_version");
+        }
+    }
+}
@@ -20,21 +20,44 @@
 //! println!("{}", String::from_utf8_lossy(&res));
 //! #
 //! # Ok(()) }) }
-//! # #[cfg(feature = "io-tokio")]
+//! # #[cfg(not(feature = "io-async-std"))]
 //! # fn main() {}
 //! ```
 //!
+//! # Handshakes
+//!
+//! The handshake of `connect` and `accept` runs the handshake of OpenSSL on top of the transport.
+//! When the transport returns `Poll::Pending`, OpenSSL sees a `WouldBlock` error and the handshake
+//! future returns `Poll::Pending` as well, keeping the state of the handshake. The transport
+//! registered the waker of the task, so the future is only polled again once the transport is
+//! ready, and resumes the handshake where it stopped. A stalled handshake therefore yields to the
+//! executor instead of polling in a loop.
+//!
+//! A transport must not return `Poll::Ready(Err(_))` with `io::ErrorKind::WouldBlock`: it can't be
+//! told apart from `Poll::Pending`, and the handshake would stall without a waker to resume it. To
+//! drive the handshake with a readiness source of your own, use the blocking API of
+//! [`sync_io`](crate::sync_io) on a nonblocking stream, and resume it with
+//! `MidHandshakeTlsStream::handshake`.
+
 mod acceptor;
 mod connector;
 mod handshake;
+mod proxy;
 pub(crate) mod runtime;
+#[cfg(feature = "hyper")]
+mod service;
+mod split;
 mod std_adapter;
 mod stream;
 
+pub use crate::Host;
 pub use accept::accept;
 pub use acceptor::TlsAcceptor;
 pub use connect::{connect, TlsConnector};
-pub use host::Host;
+#[cfg(feature = "hyper")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "hyper")))]
+pub use service::{HttpsConnector, HttpsStream};
+pub use split::{ReadHalf, WriteHalf};
 pub use stream::TlsStream;
 
 mod accept {
@@ -46,6 +69,11 @@ mod accept {
 
     /// Accept an incoming connection.
     ///
+    /// This reads and parses the identity and builds a new acceptor on every call, which is
+    /// convenient for a single connection but slow for a server accepting many of them. Build
+    /// a [`TlsAcceptor`](async_io::TlsAcceptor) once instead, and call its `accept` for each
+    /// connection.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -64,7 +92,7 @@ mod accept {
     /// // handle stream here
     /// #
     /// # Ok(()) }) }
-    /// # #[cfg(feature = "io-tokio")]
+    /// # #[cfg(not(feature = "io-async-std"))]
     /// # fn main() {}
     /// ```
     pub async fn accept<R, S, T>(file: R, password: S, stream: T) -> crate::Result<TlsStream<T>>
@@ -80,64 +108,30 @@ mod accept {
     }
 }
 
-mod host {
-    use url::Url;
-
-    /// The host part of a domain (without scheme, port and path).
-    ///
-    /// This is the argument to the `connect` function. Strings and string slices are
-    /// converted into Hosts automatically, as is [Url](url::Url) with the `host-from-url` feature (enabled by default).
-    #[derive(Debug)]
-    pub struct Host(String);
-
-    impl Host {
-        /// The host as string. Consumes self.
-        #[allow(clippy::wrong_self_convention)]
-        pub fn as_string(self) -> String {
-            self.0
-        }
-    }
-
-    impl From<&str> for Host {
-        fn from(host: &str) -> Self {
-            Self(host.into())
-        }
-    }
-
-    impl From<String> for Host {
-        fn from(host: String) -> Self {
-            Self(host)
-        }
-    }
-
-    impl From<&String> for Host {
-        fn from(host: &String) -> Self {
-            Self(host.into())
-        }
-    }
-
-    impl From<Url> for Host {
-        fn from(url: Url) -> Self {
-            Self(url.host_str().expect("URL has to include a host part.").into())
-        }
-    }
-
-    impl From<&Url> for Host {
-        fn from(url: &Url) -> Self {
-            Self(url.host_str().expect("URL has to include a host part.").into())
-        }
-    }
-}
-
 mod connect {
-    use std::fmt::{self, Debug};
+    use std::{
+        fmt::{self, Debug},
+        path::Path,
+        sync::Arc,
+        time::Duration,
+    };
 
     use crate::{async_io, sync_io};
 
-    use super::host::Host;
-    use super::runtime::{AsyncRead, AsyncWrite};
+    use openssl::{base64, ssl::SslMethod, x509::X509StoreContextRef};
+
+    use super::proxy;
+    use super::runtime::{self, AsyncRead, AsyncWrite};
     use super::TlsStream;
-    use crate::{Certificate, Identity, Protocol};
+    use crate::Host;
+    use crate::{Certificate, Identity, MaxFragmentLength, Protocol, SessionStore, SslVerifyMode};
+
+    #[cfg(feature = "io-tokio")]
+    use std::convert::TryFrom;
+    #[cfg(feature = "io-tokio")]
+    use tokio::net::TcpStream;
+    #[cfg(feature = "io-tokio")]
+    use url::Url;
 
     /// Connect a client to a remote server.
     ///
@@ -160,7 +154,7 @@ mod connect {
     /// println!("{}", String::from_utf8_lossy(&res));
     /// #
     /// # Ok(()) }) }
-    /// # #[cfg(feature = "io-tokio")]
+    /// # #[cfg(not(feature = "io-async-std"))]
     /// # fn main() {}
     /// ```
     pub async fn connect<S>(host: impl Into<Host>, stream: S) -> crate::Result<TlsStream<S>>
@@ -195,11 +189,12 @@ mod connect {
     /// println!("{}", String::from_utf8_lossy(&res));
     /// #
     /// # Ok(()) }) }
-    /// # #[cfg(feature = "io-tokio")]
+    /// # #[cfg(not(feature = "io-async-std"))]
     /// # fn main() {}
     /// ```
     pub struct TlsConnector {
         builder: sync_io::TlsConnectorBuilder,
+        proxy_authorization: Option<String>,
     }
 
     impl Default for TlsConnector {
@@ -213,19 +208,47 @@ mod connect {
         pub fn new() -> Self {
             Self {
                 builder: sync_io::TlsConnector::builder(),
+                proxy_authorization: None,
             }
         }
 
+        /// Create a new instance from a configured sync builder, exposing all of its settings
+        /// to the async connector.
+        pub fn from_builder(builder: sync_io::TlsConnectorBuilder) -> Self {
+            Self {
+                builder,
+                proxy_authorization: None,
+            }
+        }
+
+        /// Sets the OpenSSL method the connector is created with, e.g. `SslMethod::tls_client()`.
+        ///
+        /// Defaults to `SslMethod::tls()`.
+        pub fn with_method(mut self, method: SslMethod) -> Self {
+            self.builder.with_method(method);
+            self
+        }
+
         /// Sets the identity to be used for client certificate authentication.
         pub fn identity(mut self, identity: Identity) -> Self {
             self.builder.identity(identity);
             self
         }
 
+        /// Adds intermediate certificates to send along with the certificate of the identity,
+        /// in order from the root towards the issuer of the leaf certificate.
+        ///
+        /// Connecting fails if no identity is set.
+        pub fn add_client_cert_chain(mut self, certs: Vec<Certificate>) -> Self {
+            self.builder.add_client_cert_chain(certs);
+            self
+        }
+
         /// Sets the minimum supported protocol version.
         ///
         /// A value of `None` enables support for the oldest protocols supported by the
-        /// implementation. Defaults to `Some(Protocol::Tlsv10)`.
+        /// implementation. Defaults to `Some(Protocol::Tlsv12)`, TLS 1.0 and 1.1 have to be enabled
+        /// explicitly.
         pub fn min_protocol_version(mut self, protocol: Option<Protocol>) -> Self {
             self.builder.min_protocol_version(protocol);
             self
@@ -250,6 +273,76 @@ mod connect {
             self
         }
 
+        /// Adds several certificates to the set of roots that the connector will trust.
+        pub fn add_root_certificates<I>(mut self, certs: I) -> Self
+        where
+            I: IntoIterator<Item = Certificate>,
+        {
+            self.builder.add_root_certificates(certs);
+            self
+        }
+
+        /// Adds the certificates of the operating system's trust store to the set of roots, as
+        /// loaded by `rustls-native-certs`. Fails if no certificate could be loaded at all.
+        #[cfg(feature = "native-certs")]
+        #[cfg_attr(feature = "docs", doc(cfg(feature = "native-certs")))]
+        pub fn with_native_certs(mut self) -> crate::Result<Self> {
+            self.builder.with_native_certs()?;
+            Ok(self)
+        }
+
+        /// Adds every PEM-encoded certificate of a directory to the set of roots. A missing
+        /// directory and files which aren't certificates are skipped.
+        pub fn add_root_certificate_dir(mut self, dir: &Path) -> Self {
+            self.builder.add_root_certificate_dir(dir);
+            self
+        }
+
+        /// Adds the certificates of the Android system trust store to the set of roots. Done by
+        /// default on Android, and a no-op on other targets.
+        pub fn load_android_system_certs(mut self) -> Self {
+            self.builder.load_android_system_certs();
+            self
+        }
+
+        /// Sets a file of PEM-encoded certificates for the connector to trust, such as a CA
+        /// bundle pointed to by `SSL_CERT_FILE`.
+        ///
+        /// The file is loaded when the connector connects, in addition to the system's trust
+        /// roots unless `disable_built_in_roots` is set. Fails if the file doesn't exist.
+        pub fn set_ca_file(mut self, path: &Path) -> crate::Result<Self> {
+            self.builder.set_ca_file(path)?;
+            Ok(self)
+        }
+
+        /// Adds a PEM-encoded certificate revocation list, and enables checking the server
+        /// certificate against the CRLs of its issuer.
+        ///
+        /// Once a CRL is added, connecting fails with a verification error if the server
+        /// certificate is revoked, or if no CRL of its issuer was added.
+        pub fn add_crl(mut self, crl_pem: &[u8]) -> crate::Result<Self> {
+            self.builder.add_crl(crl_pem)?;
+            Ok(self)
+        }
+
+        /// Controls the use of built-in system certificates during certificate validation.
+        ///
+        /// Defaults to `false` -- built-in system certs will be used.
+        pub fn disable_built_in_roots(mut self, disable: bool) -> Self {
+            self.builder.disable_built_in_roots(disable);
+            self
+        }
+
+        /// Controls looking up the system certificate locations with `openssl-probe`, setting
+        /// `SSL_CERT_FILE` and `SSL_CERT_DIR` for OpenSSL once per process if unset, e.g. for
+        /// builds with the `vendored` feature.
+        ///
+        /// Defaults to `true`.
+        pub fn use_system_cert_env_vars(mut self, enable: bool) -> Self {
+            self.builder.use_system_cert_env_vars(enable);
+            self
+        }
+
         /// Controls the use of certificate validation.
         ///
         /// Defaults to false.
@@ -265,7 +358,41 @@ mod connect {
             self
         }
 
-        /// Controls the use of Server Name Indication (SNI).
+        /// Accepts invalid certificates, e.g. self-signed ones or issued by an unknown CA, as
+        /// long as they are not expired or not yet valid.
+        ///
+        /// # Warning
+        ///
+        /// Without further checks, such as pinning the fingerprint of the certificate, *any*
+        /// certificate for the host which is within its validity period will be trusted.
+        pub fn accept_invalid_certs_except_expiry(mut self) -> Self {
+            self.builder.accept_invalid_certs_except_expiry();
+            self
+        }
+
+        /// Sets the OpenSSL verification mode, a lower-level alternative to the other
+        /// verification settings.
+        ///
+        /// See `TlsConnectorBuilder::set_verify_mode`.
+        pub fn set_verify_mode(mut self, mode: SslVerifyMode) -> Self {
+            self.builder.set_verify_mode(mode);
+            self
+        }
+
+        /// Sets a callback deciding on the verification of each certificate of the server's
+        /// chain, e.g. to pin certificates.
+        ///
+        /// See `TlsConnectorBuilder::set_verify_callback`.
+        pub fn set_verify_callback<F>(mut self, callback: F) -> Self
+        where
+            F: Fn(bool, &mut X509StoreContextRef) -> bool + Send + Sync + 'static,
+        {
+            self.builder.set_verify_callback(callback);
+            self
+        }
+
+        /// Controls the use of Server Name Indication (SNI), independently of hostname
+        /// verification, see `sync_io::TlsConnectorBuilder::use_sni`.
         ///
         /// Defaults to `true`.
         pub fn use_sni(mut self, use_sni: bool) -> Self {
@@ -287,6 +414,169 @@ mod connect {
             self
         }
 
+        /// Controls requesting a stapled OCSP response from the server.
+        ///
+        /// The response is available from `TlsStream::ocsp_response` after the handshake. It is
+        /// not validated by the connector. Defaults to `false`.
+        pub fn set_status_request(mut self, status_request: bool) -> Self {
+            self.builder.set_status_request(status_request);
+            self
+        }
+
+        /// Controls caching sessions in memory to resume them on later connections to the same
+        /// host.
+        ///
+        /// The cache is shared by all connections of this connector. Defaults to `false`.
+        pub fn enable_session_cache(mut self, enable: bool) -> Self {
+            self.builder.enable_session_cache(enable);
+            self
+        }
+
+        /// Sets a store to cache sessions in, to resume them on later connections to the same
+        /// host.
+        ///
+        /// The store must only be shared by identically configured connectors, see
+        /// `SessionStore`.
+        pub fn set_session_store(mut self, store: Arc<dyn SessionStore>) -> Self {
+            self.builder.set_session_store(store);
+            self
+        }
+
+        /// Controls requesting session tickets from the server.
+        ///
+        /// Only affects TLS 1.2 and older. Defaults to `false`.
+        pub fn disable_tickets(mut self, disable: bool) -> Self {
+            self.builder.disable_tickets(disable);
+            self
+        }
+
+        /// Controls OpenSSL's read-ahead, reading as many bytes from the stream as are
+        /// available instead of one record at a time.
+        ///
+        /// This saves system calls on bulk transfers. Defaults to `false`.
+        pub fn set_read_ahead(mut self, read_ahead: bool) -> Self {
+            self.builder.set_read_ahead(read_ahead);
+            self
+        }
+
+        /// Sets a callback providing a pre-shared key (PSK) to authenticate with instead of
+        /// certificates.
+        ///
+        /// The callback receives the identity hint of the server, if it sent one, and returns
+        /// the PSK identity and key to use, or `None` to abort the handshake.
+        pub fn set_psk_client_callback<F>(mut self, callback: F) -> Self
+        where
+            F: Fn(Option<&[u8]>) -> Option<(Vec<u8>, Vec<u8>)> + Send + Sync + 'static,
+        {
+            self.builder.set_psk_client_callback(callback);
+            self
+        }
+
+        /// Controls TLS compression, which exposes the connection to CRIME-style attacks.
+        ///
+        /// Defaults to `true`.
+        pub fn disable_compression(mut self, disable: bool) -> Self {
+            self.builder.disable_compression(disable);
+            self
+        }
+
+        /// Controls refusing renegotiation requests of the server.
+        ///
+        /// Defaults to `false`. Requires OpenSSL 1.1.0h or newer.
+        #[cfg(have_ossl110h)]
+        pub fn disable_renegotiation(mut self, disable: bool) -> Self {
+            self.builder.disable_renegotiation(disable);
+            self
+        }
+
+        /// Controls connecting to and renegotiating with servers not supporting secure
+        /// renegotiation (RFC 5746).
+        ///
+        /// Defaults to `false`. See `TlsConnectorBuilder::allow_legacy_renegotiation` for the
+        /// risks.
+        pub fn allow_legacy_renegotiation(mut self, allow: bool) -> Self {
+            self.builder.allow_legacy_renegotiation(allow);
+            self
+        }
+
+        /// Sets the supported key exchange groups in order of preference, e.g. `"X25519:P-256"`.
+        ///
+        /// Connecting fails if the list contains a group unknown to OpenSSL. Requires OpenSSL
+        /// 1.1.1 or newer.
+        #[cfg(have_ossl111)]
+        pub fn set_groups_list(mut self, groups: &str) -> Self {
+            self.builder.set_groups_list(groups);
+            self
+        }
+
+        /// Sets the OpenSSL security level, from 0 to 5.
+        ///
+        /// Defaults to the level OpenSSL was configured with. Requires OpenSSL 1.1.0 or newer.
+        #[cfg(have_ossl110)]
+        pub fn set_security_level(mut self, level: u32) -> Self {
+            self.builder.set_security_level(level);
+            self
+        }
+
+        /// Requests a smaller maximum TLS record size from the server with the
+        /// max_fragment_length extension.
+        ///
+        /// Only limits TLS 1.2 and older. Fails if OpenSSL is older than 1.1.1.
+        pub fn set_max_fragment_length(mut self, len: MaxFragmentLength) -> crate::Result<Self> {
+            self.builder.set_max_fragment_length(len)?;
+            Ok(self)
+        }
+
+        /// Pads TLS 1.3 records to a multiple of `size` bytes, `0` disabling padding.
+        ///
+        /// Connecting fails if `size` exceeds 16384 bytes. Requires OpenSSL 1.1.1 or newer.
+        #[cfg(have_ossl111)]
+        pub fn set_block_padding(mut self, size: usize) -> Self {
+            self.builder.set_block_padding(size);
+            self
+        }
+
+        /// Sets a callback receiving the TLS key material of every connection, one line at a
+        /// time in the NSS key log format. Requires OpenSSL 1.1.1 or newer.
+        ///
+        /// # Warning
+        ///
+        /// Anyone in possession of the key log can decrypt the traffic of the logged
+        /// connections. Only use this for debugging.
+        #[cfg(have_ossl111)]
+        pub fn set_keylog_callback<F>(mut self, callback: F) -> Self
+        where
+            F: Fn(&str) + Send + Sync + 'static,
+        {
+            self.builder.set_keylog_callback(callback);
+            self
+        }
+
+        /// Controls appending the TLS key material to the file named by the `SSLKEYLOGFILE`
+        /// environment variable, if it is set.
+        ///
+        /// Defaults to `false`. Requires OpenSSL 1.1.1 or newer.
+        ///
+        /// # Warning
+        ///
+        /// Anyone in possession of the key log can decrypt the traffic of the logged
+        /// connections. Only use this for debugging.
+        #[cfg(have_ossl111)]
+        pub fn use_sslkeylogfile(mut self, enable: bool) -> Self {
+            self.builder.use_sslkeylogfile(enable);
+            self
+        }
+
+        /// Allows the server to request a client certificate after a TLS 1.3 handshake.
+        ///
+        /// The certificate of the identity is sent if the server asks for one. Requires
+        /// OpenSSL 1.1.1 or newer.
+        #[cfg(have_ossl111)]
+        pub fn enable_post_handshake_auth(mut self) -> Self {
+            self.builder.enable_post_handshake_auth();
+            self
+        }
+
         /// Connect to a remote server.
         ///
         /// # Examples
@@ -311,7 +601,7 @@ mod connect {
         /// println!("{}", String::from_utf8_lossy(&res));
         /// #
         /// # Ok(()) }) }
-        /// # #[cfg(feature = "io-tokio")]
+        /// # #[cfg(not(feature = "io-async-std"))]
         /// # fn main() {}
         /// ```
         pub async fn connect<S>(&self, host: impl Into<Host>, stream: S) -> crate::Result<TlsStream<S>>
@@ -325,6 +615,147 @@ mod connect {
             let stream = connector.connect(&domain, stream).await?;
             Ok(stream)
         }
+
+        /// Connect to a remote server without verifying that its certificate matches `host`, for
+        /// this connection only.
+        ///
+        /// The certificate chain is still validated. Unlike `danger_accept_invalid_hostnames`,
+        /// other connections made with the same connector are not affected.
+        ///
+        /// # Warning
+        ///
+        /// Any valid certificate for *any* site will be trusted for this connection. Only use
+        /// this for hosts authenticated otherwise.
+        pub async fn connect_no_hostname_check<S>(
+            &self,
+            host: impl Into<Host>,
+            stream: S,
+        ) -> crate::Result<TlsStream<S>>
+        where
+            S: AsyncRead + AsyncWrite + Unpin,
+        {
+            let domain = host.into().as_string();
+            let connector = self.builder.build()?;
+            let connector = async_io::connector::TlsConnector::from(connector);
+            let stream = connector.connect_no_hostname_check(&domain, stream).await?;
+            Ok(stream)
+        }
+
+        /// Connect to a remote server, failing with a `TimedOut` I/O error if the handshake
+        /// doesn't complete within `timeout`.
+        ///
+        /// The timer is provided by the runtime selected with the `io-tokio` or `io-async-std`
+        /// feature. With `io-futures`, the runtime-independent timer of `async-io` is used.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// # #[cfg(feature = "io-async-std")]
+        /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> { async_std::task::block_on(async {
+        /// #
+        /// use async_std::net::TcpStream;
+        /// use opentls::async_io::TlsConnector;
+        /// use std::time::Duration;
+        ///
+        /// let stream = TcpStream::connect("google.com:443").await?;
+        /// let stream = TlsConnector::new()
+        ///     .connect_timeout("google.com", stream, Duration::from_secs(5))
+        ///     .await?;
+        /// #
+        /// # Ok(()) }) }
+        /// # #[cfg(not(feature = "io-async-std"))]
+        /// # fn main() {}
+        /// ```
+        pub async fn connect_timeout<S>(
+            &self,
+            host: impl Into<Host>,
+            stream: S,
+            timeout: Duration,
+        ) -> crate::Result<TlsStream<S>>
+        where
+            S: AsyncRead + AsyncWrite + Unpin,
+        {
+            runtime::timeout(timeout, self.connect(host, stream)).await?
+        }
+
+        /// Sets the credentials to authenticate to an HTTP proxy with, see `connect_via_proxy`.
+        ///
+        /// They are sent with HTTP basic authentication, unencrypted unless the connection to
+        /// the proxy is.
+        pub fn proxy_basic_auth(mut self, username: &str, password: &str) -> Self {
+            let credentials = base64::encode_block(format!("{}:{}", username, password).as_bytes());
+            self.proxy_authorization = Some(format!("Basic {}", credentials));
+            self
+        }
+
+        /// Connect to a remote server through an HTTP proxy, with `stream` connected to the
+        /// proxy.
+        ///
+        /// The proxy is asked with a `CONNECT` request to open a tunnel to `host` and `port`,
+        /// through which the TLS handshake is performed. If the proxy answers with another
+        /// status than `2xx`, the connection fails with `Error::Proxy`. A host with characters
+        /// not allowed in a host name, such as whitespace, is rejected with an `InvalidInput`
+        /// error before anything is sent.
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// # #[cfg(feature = "io-async-std")]
+        /// # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> { async_std::task::block_on(async {
+        /// #
+        /// use async_std::net::TcpStream;
+        /// use opentls::async_io::TlsConnector;
+        ///
+        /// let stream = TcpStream::connect("proxy.example.com:3128").await?;
+        /// let stream = TlsConnector::new()
+        ///     .proxy_basic_auth("user", "secret")
+        ///     .connect_via_proxy("google.com", 443, stream)
+        ///     .await?;
+        /// #
+        /// # Ok(()) }) }
+        /// # #[cfg(not(feature = "io-async-std"))]
+        /// # fn main() {}
+        /// ```
+        pub async fn connect_via_proxy<S>(
+            &self,
+            host: impl Into<Host>,
+            port: u16,
+            mut stream: S,
+        ) -> crate::Result<TlsStream<S>>
+        where
+            S: AsyncRead + AsyncWrite + Unpin,
+        {
+            let domain = host.into().as_string();
+            proxy::tunnel(&mut stream, &domain, port, self.proxy_authorization.as_deref()).await?;
+            self.connect(domain, stream).await
+        }
+
+        /// Resolves the host of the URL, opens a TCP connection to it and connects over it.
+        ///
+        /// The port defaults to the one of the URL's scheme, or to 443 if the scheme is not
+        /// known. With the `hyper` feature, `HttpsConnector` connects the clients of `hyper`.
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// # #[tokio::main]
+        /// # async fn main() -> opentls::Result<()> {
+        /// use opentls::async_io::TlsConnector;
+        /// use url::Url;
+        ///
+        /// let url = Url::parse("https://google.com/").unwrap();
+        /// let stream = TlsConnector::new().connect_url(&url).await?;
+        /// # Ok(()) }
+        /// ```
+        #[cfg(feature = "io-tokio")]
+        #[cfg_attr(feature = "docs", doc(cfg(feature = "io-tokio")))]
+        pub async fn connect_url(&self, url: &Url) -> crate::Result<TlsStream<TcpStream>> {
+            let domain = Host::try_from(url)?.as_string();
+            let port = url.port_or_known_default().unwrap_or(443);
+
+            let stream = TcpStream::connect((domain.as_str(), port)).await?;
+            self.connect(domain, stream).await
+        }
     }
 
     impl Debug for TlsConnector {
@@ -335,7 +766,7 @@ mod connect {
 
     impl From<sync_io::TlsConnectorBuilder> for TlsConnector {
         fn from(builder: sync_io::TlsConnectorBuilder) -> Self {
-            Self { builder }
+            Self::from_builder(builder)
         }
     }
 }
@@ -13,6 +13,10 @@ use crate::{
 /// A wrapper around a `native_tls::TlsAcceptor`, providing an async `accept`
 /// method.
 ///
+/// The acceptor is meant to be built once and used for every connection. Cloning it is cheap,
+/// the clones share the same reference-counted OpenSSL context, so the identity isn't parsed
+/// again.
+///
 /// # Example
 ///
 /// ```no_run
@@ -39,7 +43,7 @@ use crate::{
 /// }
 /// #
 /// # Ok(()) }) }
-/// # #[cfg(feature = "io-tokio")]
+/// # #[cfg(not(feature = "io-async-std"))]
 /// # fn main() {}
 /// ```
 #[derive(Clone)]
@@ -47,15 +51,33 @@ pub struct TlsAcceptor(sync_io::TlsAcceptor);
 
 impl TlsAcceptor {
     /// Create a new TlsAcceptor based on an identity file and matching password.
+    ///
+    /// With the `zeroize` feature, the contents of the file are scrubbed from memory once
+    /// the identity is parsed.
     pub async fn new<R, S>(mut file: R, password: S) -> crate::Result<Self>
     where
         R: AsyncRead + Unpin,
         S: AsRef<str>,
     {
-        let mut identity = vec![];
-        file.read_to_end(&mut identity).await?;
+        #[cfg(not(feature = "zeroize"))]
+        let identity = {
+            let mut identity = vec![];
+            file.read_to_end(&mut identity).await?;
+            identity
+        };
+
+        #[cfg(feature = "zeroize")]
+        let identity = read_zeroizing(&mut file).await?;
 
         let identity = Identity::from_pkcs12(&identity, password.as_ref())?;
+        Self::from_identity(identity)
+    }
+
+    /// Create a new TlsAcceptor from an already parsed identity.
+    ///
+    /// Parsing the identity once allows building several acceptors from it, e.g. when the
+    /// certificates are reloaded periodically.
+    pub fn from_identity(identity: Identity) -> crate::Result<Self> {
         Ok(TlsAcceptor(sync_io::TlsAcceptor::new(identity)?))
     }
 
@@ -78,6 +100,24 @@ impl TlsAcceptor {
     }
 }
 
+/// Reads `reader` to the end into a buffer scrubbed when dropped.
+#[cfg(feature = "zeroize")]
+async fn read_zeroizing<R>(reader: &mut R) -> std::io::Result<crate::zeroize::Zeroizing>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = crate::zeroize::Zeroizing::default();
+    let mut chunk = crate::zeroize::Zeroizing::default();
+    chunk.extend_from_slice(&[0; 4096]);
+
+    loop {
+        match reader.read(&mut chunk).await? {
+            0 => return Ok(buf),
+            n => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
 impl fmt::Debug for TlsAcceptor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TlsAcceptor").finish()
@@ -90,15 +130,57 @@ impl From<sync_io::TlsAcceptor> for TlsAcceptor {
     }
 }
 
-#[cfg(all(test, feature = "io-async-std"))]
+#[cfg(all(test, any(feature = "io-async-std", feature = "io-futures")))]
 mod tests {
     use super::*;
-    use crate::async_io::{runtime::AsyncWriteExt, TlsConnector};
+    use crate::async_io::{runtime::AsyncWriteExt, TlsConnector, TlsStream};
     use async_std::{
         fs::File,
         net::{TcpListener, TcpStream},
         stream::StreamExt,
+        task::JoinHandle,
     };
+    use std::{future::Future, net::SocketAddr};
+
+    /// An acceptor with the identity of `tests/identity.pfx`, listening on a free port.
+    async fn listen() -> (TlsAcceptor, TcpListener, SocketAddr) {
+        listen_with("tests/identity.pfx", "hello").await
+    }
+
+    /// An acceptor with the PKCS #12 identity at `path`, listening on a free port.
+    async fn listen_with(path: &str, password: &str) -> (TlsAcceptor, TcpListener, SocketAddr) {
+        let key = File::open(path).await.unwrap();
+        let acceptor = TlsAcceptor::new(key, password).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        (acceptor, listener, addr)
+    }
+
+    /// Accepts a single connection, handing the stream to `f`.
+    async fn serve_once<F, Fut, T>(f: F) -> (SocketAddr, JoinHandle<T>)
+    where
+        F: FnOnce(TlsStream<TcpStream>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send,
+        T: Send + 'static,
+    {
+        let (acceptor, listener, addr) = listen().await;
+
+        let server = async_std::task::spawn(async move {
+            let stream = listener.incoming().next().await.unwrap().unwrap();
+            let stream = acceptor.accept(stream).await.unwrap();
+            f(stream).await
+        });
+
+        (addr, server)
+    }
+
+    /// Connects to `addr`, accepting the untrusted certificate of the test identity.
+    async fn connect(addr: SocketAddr) -> TlsStream<TcpStream> {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::new().danger_accept_invalid_certs(true);
+        connector.connect("127.0.0.1", stream).await.unwrap()
+    }
 
     #[async_std::test]
     async fn test_acceptor() {
@@ -126,4 +208,632 @@ mod tests {
         stream.read_to_end(&mut res).await.unwrap();
         assert_eq!(res, b"hello");
     }
+
+    #[async_std::test]
+    async fn test_accept_many() {
+        let identity = Identity::from_pkcs12(include_bytes!("../../tests/identity.pfx"), "hello").unwrap();
+        let acceptor = TlsAcceptor::from_identity(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async_std::task::spawn(async move {
+            let mut tasks = vec![];
+
+            for _ in 0..3 {
+                let stream = listener.incoming().next().await.unwrap().unwrap();
+                let acceptor = acceptor.clone();
+
+                tasks.push(async_std::task::spawn(async move {
+                    let mut stream = acceptor.accept(stream).await.unwrap();
+                    stream.write_all(b"hello").await.unwrap();
+                }));
+            }
+
+            for task in tasks {
+                task.await;
+            }
+        });
+
+        for _ in 0..3 {
+            let mut stream = connect(addr).await;
+
+            let mut res = Vec::new();
+            stream.read_to_end(&mut res).await.unwrap();
+            assert_eq!(res, b"hello");
+        }
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_from_identity() {
+        let identity = Identity::from_pkcs12(include_bytes!("../../tests/identity.pfx"), "hello").unwrap();
+        let acceptor = TlsAcceptor::from_identity(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async_std::task::spawn(async move {
+            let stream = listener.incoming().next().await.unwrap().unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+        });
+
+        let mut stream = connect(addr).await;
+
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).await.unwrap();
+        assert_eq!(res, b"hello");
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_peer_certificate() {
+        let (addr, server) = serve_once(|mut stream| async move {
+            assert!(stream.peer_certificate().unwrap().is_none());
+
+            let binding = stream.tls_server_end_point().unwrap().unwrap();
+            stream.write_all(&binding).await.unwrap();
+        })
+        .await;
+
+        let mut stream = connect(addr).await;
+        assert!(stream.peer_certificate().unwrap().is_some());
+
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).await.unwrap();
+        assert_eq!(Some(res), stream.tls_server_end_point().unwrap());
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_shutdown() {
+        let (addr, server) = serve_once(|mut stream| async move {
+            let mut res = Vec::new();
+            stream.read_to_end(&mut res).await.unwrap();
+            assert_eq!(res, b"hello");
+
+            stream.close().await.unwrap();
+        })
+        .await;
+
+        let mut stream = connect(addr).await;
+        stream.write_all(b"hello").await.unwrap();
+        stream.close().await.unwrap();
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_shutdown_peer_dropped() {
+        let (addr, server) = serve_once(|mut stream| async move {
+            let mut res = Vec::new();
+            stream.read_to_end(&mut res).await.unwrap();
+        })
+        .await;
+
+        let mut stream = connect(addr).await;
+        stream.close().await.unwrap();
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_close_unanswered() {
+        // The server keeps the connection open without answering the close_notify.
+        let (addr, server) = serve_once(|mut stream| async move {
+            let mut res = Vec::new();
+            stream.read_to_end(&mut res).await.unwrap();
+            stream
+        })
+        .await;
+
+        let mut stream = connect(addr).await;
+        stream.close().await.unwrap();
+
+        let _stream = server.await;
+    }
+
+    #[async_std::test]
+    async fn test_shutdown_bidirectional() {
+        let (acceptor, listener, addr) = listen().await;
+
+        let server = async_std::task::spawn(async move {
+            for data in [&b""[..], b"late"].iter() {
+                let stream = listener.incoming().next().await.unwrap().unwrap();
+                let mut stream = acceptor.accept(stream).await.unwrap();
+
+                stream.write_all(data).await.unwrap();
+                let mut res = Vec::new();
+                stream.read_to_end(&mut res).await.unwrap();
+                stream.close().await.unwrap();
+            }
+        });
+
+        let mut stream = connect(addr).await;
+        stream.shutdown_bidirectional().await.unwrap();
+
+        // Data sent by the peer before its close_notify isn't discarded silently.
+        let mut stream = connect(addr).await;
+        let err = stream.shutdown_bidirectional().await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_buf_read() {
+        use futures_util::io::AsyncBufReadExt;
+
+        let (addr, server) = serve_once(|mut stream| async move {
+            stream.write_all(b"hello\nworld\n").await.unwrap();
+            stream.write_all(b"split ").await.unwrap();
+            stream.write_all(b"line\n").await.unwrap();
+        })
+        .await;
+
+        let mut stream = connect(addr).await;
+
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "hello\n");
+        assert_eq!(stream.buffered_read_size().unwrap(), 6);
+        assert_eq!(stream.bytes_read(), 6);
+
+        line.clear();
+        stream.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "world\n");
+
+        // The line spans two records.
+        line.clear();
+        stream.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "split line\n");
+
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_into_split() {
+        let (addr, server) = serve_once(|mut stream| async move {
+            let mut buf = [0; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            stream.write_all(b"world").await.unwrap();
+        })
+        .await;
+
+        let (mut reader, mut writer) = connect(addr).await.into_split();
+
+        let read = async_std::task::spawn(async move {
+            let mut res = Vec::new();
+            reader.read_to_end(&mut res).await.unwrap();
+            assert_eq!(res, b"world");
+            reader
+        });
+
+        writer.write_all(b"hello").await.unwrap();
+        let reader = read.await;
+
+        assert!(reader.reunite(writer).is_ok());
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_from_builder() {
+        // Sessions are only resumed by connectors verifying the server, so use a trusted one.
+        let (acceptor, listener, addr) = listen_with("tests/ip.p12", "mypass").await;
+
+        let server = async_std::task::spawn(async move {
+            for _ in 0..2 {
+                let stream = listener.incoming().next().await.unwrap().unwrap();
+                let mut stream = acceptor.accept(stream).await.unwrap();
+                stream.write_all(b"hello").await.unwrap();
+                stream.close().await.unwrap();
+            }
+        });
+
+        let cert = crate::Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")).unwrap();
+        let mut builder = sync_io::TlsConnector::builder();
+        builder.disable_built_in_roots(true).add_root_certificate(cert);
+        let connector = TlsConnector::from_builder(builder).enable_session_cache(true);
+
+        for reused in &[false, true] {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut stream = connector.connect("127.0.0.1", stream).await.unwrap();
+
+            let mut res = Vec::new();
+            stream.read_to_end(&mut res).await.unwrap();
+            assert_eq!(res, b"hello");
+            assert_eq!(stream.session_reused(), *reused);
+            stream.close().await.unwrap();
+        }
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_connection_info() {
+        let (addr, server) = serve_once(|mut stream| async move {
+            stream.write_all(b"hello").await.unwrap();
+        })
+        .await;
+
+        let info = connect(addr).await.connection_info().unwrap();
+        assert_eq!(info.protocol, "TLSv1.2");
+        assert!(info.cipher.is_some());
+        assert!(info.peer_certificate.is_some());
+        assert!(!info.session_reused);
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_disable_built_in_roots() {
+        let (acceptor, listener, addr) = listen().await;
+
+        let server = async_std::task::spawn(async move {
+            let stream = listener.incoming().next().await.unwrap().unwrap();
+            let _ = acceptor.accept(stream).await;
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::new().disable_built_in_roots(true);
+
+        match connector.connect("foobar.com", stream).await {
+            Err(e) => assert!(e.is_verify_error()),
+            Ok(_) => panic!("handshake succeeded without any trusted roots"),
+        }
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_connect_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = async_std::task::spawn(async move {
+            // Accept the connection, but never answer the handshake.
+            let (stream, _) = listener.accept().await.unwrap();
+            async_std::task::sleep(std::time::Duration::from_secs(1)).await;
+            drop(stream);
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::new();
+        let timeout = std::time::Duration::from_millis(100);
+
+        match connector.connect_timeout("127.0.0.1", stream, timeout).await {
+            Err(crate::Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(_) => panic!("handshake should time out"),
+        }
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_verify_callback() {
+        let (acceptor, listener, addr) = listen().await;
+
+        let server = async_std::task::spawn(async move {
+            let mut incoming = listener.incoming();
+
+            let stream = incoming.next().await.unwrap().unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+            drop(stream);
+
+            let stream = incoming.next().await.unwrap().unwrap();
+            assert!(acceptor.accept(stream).await.is_err());
+        });
+
+        // The callback overrides the failed verification of the untrusted certificate.
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::new().set_verify_callback(|_, _| true);
+        let mut stream = connector.connect("127.0.0.1", stream).await.unwrap();
+
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).await.unwrap();
+        assert_eq!(res, b"hello");
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::new().set_verify_callback(|_, _| false);
+        assert!(connector.connect("127.0.0.1", stream).await.is_err());
+
+        server.await;
+    }
+
+    #[async_std::test]
+    async fn test_write_vectored() {
+        let (addr, server) = serve_once(|mut stream| async move {
+            let bufs = [std::io::IoSlice::new(b"head"), std::io::IoSlice::new(b"body")];
+            assert_eq!(stream.write_vectored(&bufs).await.unwrap(), 8);
+        })
+        .await;
+
+        let mut stream = connect(addr).await;
+
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).await.unwrap();
+        assert_eq!(res, b"headbody");
+
+        server.await;
+    }
+}
+
+#[cfg(all(test, feature = "io-tokio"))]
+mod tokio_tests {
+    use super::*;
+    use crate::async_io::{TlsConnector, TlsStream};
+    use std::{
+        io,
+        net::SocketAddr,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        task::JoinHandle,
+    };
+
+    /// An acceptor with the identity of the PKCS #12 archive `der`, listening on a free port.
+    async fn listen(der: &[u8]) -> (TlsAcceptor, TcpListener, SocketAddr) {
+        let identity = Identity::from_pkcs12(der, "mypass").unwrap();
+        let acceptor = TlsAcceptor::from_identity(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        (acceptor, listener, addr)
+    }
+
+    /// Accepts a single connection with the identity of `tests/ip.p12`, writes `hello` and
+    /// closes it.
+    async fn serve_hello_ip() -> (SocketAddr, JoinHandle<()>) {
+        let (acceptor, listener, addr) = listen(include_bytes!("../../tests/ip.p12")).await;
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        (addr, server)
+    }
+
+    /// A connector trusting only the certificate of `tests/ip.p12`.
+    fn ip_connector() -> TlsConnector {
+        let cert = crate::Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")).unwrap();
+        TlsConnector::new()
+            .disable_built_in_roots(true)
+            .add_root_certificate(cert)
+    }
+
+    /// The bounds `tokio_util::codec::Framed` needs to implement `Stream` and `Sink`.
+    fn assert_framed<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static>() {}
+
+    #[test]
+    fn test_framed_bounds() {
+        assert_framed::<TlsStream<TcpStream>>();
+    }
+
+    #[tokio::test]
+    async fn test_length_delimited_frame() {
+        let (acceptor, listener, addr) = listen(include_bytes!("../../tests/identity.p12")).await;
+
+        // Echoes one frame, prefixed with its length as `LengthDelimitedCodec` does.
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+
+            let len = stream.read_u32().await.unwrap();
+            let mut frame = vec![0; len as usize];
+            stream.read_exact(&mut frame).await.unwrap();
+
+            stream.write_u32(len).await.unwrap();
+            stream.write_all(&frame).await.unwrap();
+            stream.flush().await.unwrap();
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::new().danger_accept_invalid_certs(true);
+        let mut stream = connector.connect("127.0.0.1", stream).await.unwrap();
+
+        let frame = vec![7; 40 * 1024];
+        stream.write_u32(frame.len() as u32).await.unwrap();
+        stream.write_all(&frame).await.unwrap();
+        stream.flush().await.unwrap();
+
+        let len = stream.read_u32().await.unwrap();
+        let mut res = vec![0; len as usize];
+        stream.read_exact(&mut res).await.unwrap();
+        assert_eq!(res, frame);
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flush_buffered_transport() {
+        let (acceptor, listener, addr) = listen(include_bytes!("../../tests/identity.p12")).await;
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+            stream.write_all(b"pong").await.unwrap();
+            stream.flush().await.unwrap();
+        });
+
+        // The transport holds back writes until it is flushed, so the request only reaches the
+        // server if flushing the TLS stream flushes the transport.
+        let stream = tokio::io::BufWriter::new(TcpStream::connect(addr).await.unwrap());
+        let connector = TlsConnector::new().danger_accept_invalid_certs(true);
+        let mut stream = connector.connect("127.0.0.1", stream).await.unwrap();
+
+        stream.write_all(b"ping").await.unwrap();
+        stream.flush().await.unwrap();
+
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        server.await.unwrap();
+    }
+
+    /// A transport counting how often it is polled for reading.
+    struct CountingStream {
+        inner: TcpStream,
+        reads: Arc<AtomicUsize>,
+    }
+
+    impl tokio::io::AsyncRead for CountingStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            ctx: &mut Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            Pin::new(&mut self.inner).poll_read(ctx, buf)
+        }
+    }
+
+    impl tokio::io::AsyncWrite for CountingStream {
+        fn poll_write(mut self: Pin<&mut Self>, ctx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(ctx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(ctx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(ctx)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_handshake_yields() {
+        let (acceptor, listener, addr) = listen(include_bytes!("../../tests/identity.p12")).await;
+
+        // The server only answers the client hello after a while.
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+        });
+
+        let reads = Arc::new(AtomicUsize::new(0));
+        let stream = CountingStream {
+            inner: TcpStream::connect(addr).await.unwrap(),
+            reads: reads.clone(),
+        };
+        let connector = TlsConnector::new().danger_accept_invalid_certs(true);
+        let mut stream = connector.connect("127.0.0.1", stream).await.unwrap();
+
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+
+        // The handshake waited for the server instead of polling the transport in a loop.
+        assert!(reads.load(Ordering::SeqCst) < 20);
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_url() {
+        let (addr, server) = serve_hello_ip().await;
+        let connector = ip_connector();
+
+        let url = url::Url::parse(&format!("https://127.0.0.1:{}/", addr.port())).unwrap();
+        let mut stream = connector.connect_url(&url).await.unwrap();
+
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).await.unwrap();
+        assert_eq!(res, b"hello");
+
+        // The shutdown of the server waits for our close_notify or the connection to close.
+        drop(stream);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "hyper")]
+    async fn test_https_connector() {
+        use crate::async_io::HttpsConnector;
+        use tower_service::Service;
+
+        let (addr, server) = serve_hello_ip().await;
+        let mut service = HttpsConnector::new(ip_connector());
+
+        let uri = "http://127.0.0.1/".parse().unwrap();
+        match service.call(uri).await {
+            Err(crate::Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let uri = format!("https://127.0.0.1:{}/", addr.port()).parse().unwrap();
+        let mut stream = service.call(uri).await.unwrap();
+
+        let mut res = Vec::new();
+        stream.get_mut().read_to_end(&mut res).await.unwrap();
+        assert_eq!(res, b"hello");
+
+        drop(stream);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "hyper")]
+    async fn test_https_connector_hyper_client() {
+        use crate::async_io::HttpsConnector;
+        use http_body_util::{BodyExt, Empty};
+        use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+
+        let (acceptor, listener, addr) = listen(include_bytes!("../../tests/ip.p12")).await;
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            assert!(request.starts_with(b"GET /hello HTTP/1.1\r\n"));
+
+            let response = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\nconnection: close\r\n\r\nhello";
+            stream.write_all(response).await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        let connector = HttpsConnector::new(ip_connector());
+        let client = Client::builder(TokioExecutor::new()).build::<_, Empty<bytes::Bytes>>(connector);
+
+        let uri = format!("https://127.0.0.1:{}/hello", addr.port()).parse().unwrap();
+        let response = client.get(uri).await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"hello");
+
+        server.await.unwrap();
+    }
 }
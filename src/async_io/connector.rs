@@ -21,6 +21,14 @@ impl TlsConnector {
     {
         handshake(move |s| self.0.connect(domain, s), stream).await
     }
+
+    /// Connects the provided stream without verifying that the certificate matches the domain.
+    pub(crate) async fn connect_no_hostname_check<S>(&self, domain: &str, stream: S) -> Result<TlsStream<S>, Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        handshake(move |s| self.0.connect_no_hostname_check(domain, s), stream).await
+    }
 }
 
 impl fmt::Debug for TlsConnector {
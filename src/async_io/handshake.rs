@@ -81,7 +81,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Future for MidHandshake<S> {
 
         s.get_mut().context = cx as *mut _ as *mut ();
         match s.handshake().map_err(HandshakeError::from) {
-            Ok(stream) => Poll::Ready(Ok(TlsStream::new(sync_io::TlsStream(stream)))),
+            Ok(stream) => Poll::Ready(Ok(TlsStream::new(sync_io::TlsStream::new(stream)))),
             Err(HandshakeError::Failure(e)) => Poll::Ready(Err(e)),
             Err(HandshakeError::WouldBlock(mut s)) => {
                 s.get_mut().context = null_mut();
@@ -0,0 +1,233 @@
+use crate::{
+    async_io::runtime::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    Error,
+};
+use std::{io, marker::Unpin};
+
+/// The maximum size of the response head of the proxy.
+const MAX_RESPONSE_SIZE: usize = 8 * 1024;
+
+/// Asks the HTTP proxy connected to with `stream` to open a tunnel to `host` and `port`.
+///
+/// `authorization` is sent as the value of the `Proxy-Authorization` header.
+pub(crate) async fn tunnel<S>(stream: &mut S, host: &str, port: u16, authorization: Option<&str>) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if !valid_host(host) {
+        let err = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid characters in the proxy target host",
+        );
+        return Err(err.into());
+    }
+
+    // IPv6 addresses have to be bracketed in front of the port.
+    let target = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+
+    match status(&read_response_head(stream).await?)? {
+        200..=299 => Ok(()),
+        status => Err(Error::Proxy(status)),
+    }
+}
+
+/// Whether `host` is a registered name or an IP address, IPv6 ones without brackets, that can
+/// be put into the request line and `Host` header as-is.
+///
+/// Anything else, e.g. a line break injecting headers into the request, is rejected.
+fn valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=%:".contains(&b))
+}
+
+/// Reads the response up to the empty line ending its headers.
+///
+/// The response is read byte by byte, so nothing the server sends through the tunnel is
+/// consumed.
+async fn read_response_head<S>(stream: &mut S) -> io::Result<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut head = Vec::new();
+    let mut byte = [0];
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() == MAX_RESPONSE_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "proxy response too large"));
+        }
+
+        stream.read_exact(&mut byte).await?;
+        head.push(byte[0]);
+    }
+
+    Ok(head)
+}
+
+/// Parses the status code out of a status line such as `HTTP/1.1 200 Connection established`.
+fn status(head: &[u8]) -> io::Result<u16> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid proxy response");
+
+    let line = head.split(|&b| b == b'\r').next().ok_or_else(invalid)?;
+    let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+    let mut parts = line.split(' ');
+
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/1.") && status.len() == 3 => {
+            status.parse().map_err(|_| invalid())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_status() {
+        assert_eq!(status(b"HTTP/1.1 200 Connection established\r\n\r\n").unwrap(), 200);
+        assert_eq!(
+            status(b"HTTP/1.0 407 Proxy Authentication Required\r\n\r\n").unwrap(),
+            407
+        );
+        assert_eq!(status(b"HTTP/1.1 200\r\n\r\n").unwrap(), 200);
+        assert!(status(b"SSH-2.0-OpenSSH\r\n\r\n").is_err());
+        assert!(status(b"HTTP/1.1 2000 OK\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn validate_host() {
+        assert!(valid_host("foobar.com"));
+        assert!(valid_host("127.0.0.1"));
+        assert!(valid_host("::1"));
+        assert!(valid_host("xn--bcher-kva.example"));
+
+        assert!(!valid_host(""));
+        assert!(!valid_host("foobar.com\r\nX-Injected: 1"));
+        assert!(!valid_host("foobar.com\n"));
+        assert!(!valid_host("foobar.com 443"));
+        assert!(!valid_host("foobar.com\t"));
+        assert!(!valid_host("foo/bar"));
+        assert!(!valid_host("[::1]"));
+        assert!(!valid_host("bücher.example"));
+    }
+
+    #[cfg(any(feature = "io-async-std", feature = "io-futures"))]
+    mod tunnel {
+        use crate::async_io::{runtime::AsyncWriteExt, TlsAcceptor, TlsConnector};
+        use async_std::{
+            fs::File,
+            net::{TcpListener, TcpStream},
+            stream::StreamExt,
+        };
+        use futures_util::io::AsyncReadExt;
+        use std::io;
+
+        async fn read_request_head(stream: &mut TcpStream) -> String {
+            let mut head = Vec::new();
+            let mut byte = [0];
+
+            while !head.ends_with(b"\r\n\r\n") {
+                stream.read_exact(&mut byte).await.unwrap();
+                head.push(byte[0]);
+            }
+
+            String::from_utf8(head).unwrap()
+        }
+
+        #[async_std::test]
+        async fn test_connect_via_proxy() {
+            let key = File::open("tests/identity.pfx").await.unwrap();
+            let acceptor = TlsAcceptor::new(key, "hello").await.unwrap();
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            // The proxy opens the tunnel to itself.
+            let server = async_std::task::spawn(async move {
+                let mut stream = listener.incoming().next().await.unwrap().unwrap();
+                assert_eq!(
+                    read_request_head(&mut stream).await,
+                    "CONNECT foobar.com:443 HTTP/1.1\r\nHost: foobar.com:443\r\n\
+                     Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n\r\n"
+                );
+                stream
+                    .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                    .await
+                    .unwrap();
+
+                let mut stream = acceptor.accept(stream).await.unwrap();
+                stream.write_all(b"hello").await.unwrap();
+            });
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let connector = TlsConnector::new()
+                .danger_accept_invalid_certs(true)
+                .proxy_basic_auth("user", "secret");
+            let mut stream = connector.connect_via_proxy("foobar.com", 443, stream).await.unwrap();
+
+            let mut res = Vec::new();
+            stream.read_to_end(&mut res).await.unwrap();
+            assert_eq!(res, b"hello");
+
+            server.await;
+        }
+
+        #[async_std::test]
+        async fn test_connect_via_proxy_refused() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let server = async_std::task::spawn(async move {
+                let mut stream = listener.incoming().next().await.unwrap().unwrap();
+                read_request_head(&mut stream).await;
+                stream
+                    .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                    .await
+                    .unwrap();
+            });
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let connector = TlsConnector::new();
+
+            match connector.connect_via_proxy("foobar.com", 443, stream).await {
+                Err(crate::Error::Proxy(status)) => assert_eq!(status, 407),
+                Err(e) => panic!("unexpected error: {:?}", e),
+                Ok(_) => panic!("connected through a refused tunnel"),
+            }
+
+            server.await;
+        }
+
+        #[async_std::test]
+        async fn test_connect_via_proxy_invalid_host() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+            let connector = TlsConnector::new();
+
+            match connector
+                .connect_via_proxy("foobar.com\r\nX-Injected: 1", 443, stream)
+                .await
+            {
+                Err(crate::Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+                Err(e) => panic!("unexpected error: {:?}", e),
+                Ok(_) => panic!("connected to an invalid host"),
+            }
+        }
+    }
+}
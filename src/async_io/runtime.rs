@@ -1,9 +1,42 @@
-#[cfg(feature = "io-async-std")]
-pub(crate) use futures_util::io::{AsyncRead, AsyncWrite};
+#[cfg(any(feature = "io-async-std", feature = "io-futures"))]
+pub(crate) use futures_util::io::{AsyncBufRead, AsyncRead, AsyncWrite};
 
-#[cfg(feature = "io-async-std")]
+#[cfg(any(feature = "io-async-std", feature = "io-futures"))]
 #[allow(unused_imports)]
 pub(crate) use futures_util::io::{AsyncReadExt, AsyncWriteExt};
 
 #[cfg(feature = "io-tokio")]
-pub(crate) use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+pub(crate) use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use std::{future::Future, io, time::Duration};
+
+/// Resolves to the output of `future`, or a `TimedOut` error if it doesn't complete in time.
+#[cfg(feature = "io-async-std")]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> io::Result<F::Output> {
+    async_std::future::timeout(duration, future)
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::TimedOut, e))
+}
+
+/// Resolves to the output of `future`, or a `TimedOut` error if it doesn't complete in time.
+#[cfg(feature = "io-tokio")]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> io::Result<F::Output> {
+    tokio::time::timeout(duration, future)
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::TimedOut, e))
+}
+
+/// Resolves to the output of `future`, or a `TimedOut` error if it doesn't complete in time.
+///
+/// Uses the timer of `async-io`, which works without any particular executor.
+#[cfg(feature = "io-futures")]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> io::Result<F::Output> {
+    use futures_util::future::{self, Either};
+
+    futures_util::pin_mut!(future);
+
+    match future::select(future, async_io::Timer::after(duration)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "future timed out")),
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    async_io::{TlsConnector, TlsStream},
+    Error, Host,
+};
+use http::Uri;
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper_util::{
+    client::legacy::connect::{Connected, Connection, HttpConnector},
+    rt::TokioIo,
+};
+use std::{
+    error::Error as StdError,
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower_service::Service;
+
+/// A connector for `hyper` clients, running the handshake of a `TlsConnector` over the
+/// connections of an inner connector.
+///
+/// The inner connector opens the connection to the host and port of the URI, by default the
+/// `HttpConnector` of `hyper-util`. The host of the URI is verified against the certificate of
+/// the server.
+///
+/// Only `https` URIs, or URIs without a scheme, are accepted.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use http_body_util::Empty;
+/// use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+/// use opentls::async_io::{HttpsConnector, TlsConnector};
+///
+/// let connector = HttpsConnector::new(TlsConnector::new());
+/// let client = Client::builder(TokioExecutor::new()).build::<_, Empty<bytes::Bytes>>(connector);
+/// let response = client.get("https://google.com/".parse()?).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct HttpsConnector<T = HttpConnector> {
+    http: T,
+    tls: Arc<TlsConnector>,
+}
+
+impl HttpsConnector {
+    /// Creates a connector opening the connections with a new `HttpConnector`.
+    pub fn new(tls: TlsConnector) -> Self {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+
+        Self::from_parts(http, tls)
+    }
+}
+
+impl<T> HttpsConnector<T> {
+    /// Creates a connector running the handshake of `tls` over the connections opened by
+    /// `http`.
+    ///
+    /// An `HttpConnector` has to be told not to reject `https` URIs with
+    /// `HttpConnector::enforce_http(false)`.
+    pub fn from_parts(http: T, tls: TlsConnector) -> Self {
+        Self {
+            http,
+            tls: Arc::new(tls),
+        }
+    }
+}
+
+impl<T> fmt::Debug for HttpsConnector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpsConnector").finish()
+    }
+}
+
+impl From<TlsConnector> for HttpsConnector {
+    fn from(tls: TlsConnector) -> Self {
+        Self::new(tls)
+    }
+}
+
+impl<T> Service<Uri> for HttpsConnector<T>
+where
+    T: Service<Uri>,
+    T::Response: Read + Write + Connection + Unpin + Send + 'static,
+    T::Future: Send + 'static,
+    T::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Response = HttpsStream<T::Response>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(ctx).map_err(connect_error)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        if matches!(uri.scheme_str(), Some(scheme) if scheme != "https") {
+            let err = io::Error::new(io::ErrorKind::InvalidInput, "URI scheme has to be https.");
+            return Box::pin(async move { Err(err.into()) });
+        }
+
+        let host = match uri.host() {
+            Some(host) => Host::from(host).as_string(),
+            None => {
+                let err = io::Error::new(io::ErrorKind::InvalidInput, "URI has to include a host part.");
+                return Box::pin(async move { Err(err.into()) });
+            }
+        };
+
+        let connecting = self.http.call(uri);
+        let tls = self.tls.clone();
+
+        Box::pin(async move {
+            let stream = connecting.await.map_err(connect_error)?;
+            let stream = tls.connect(host, TokioIo::new(stream)).await?;
+
+            Ok(HttpsStream(TokioIo::new(stream)))
+        })
+    }
+}
+
+fn connect_error(err: impl Into<Box<dyn StdError + Send + Sync>>) -> Error {
+    io::Error::other(err).into()
+}
+
+/// A TLS connection opened by `HttpsConnector`, usable as the connection of a `hyper` client.
+pub struct HttpsStream<T>(TokioIo<TlsStream<TokioIo<T>>>);
+
+impl<T> HttpsStream<T> {
+    /// Returns a shared reference to the TLS stream.
+    pub fn get_ref(&self) -> &TlsStream<TokioIo<T>> {
+        self.0.inner()
+    }
+
+    /// Returns a mutable reference to the TLS stream.
+    pub fn get_mut(&mut self) -> &mut TlsStream<TokioIo<T>> {
+        self.0.inner_mut()
+    }
+}
+
+impl<T> fmt::Debug for HttpsStream<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("HttpsStream").field(self.get_ref()).finish()
+    }
+}
+
+impl<T> Read for HttpsStream<T>
+where
+    T: Read + Write + Unpin,
+{
+    fn poll_read(mut self: Pin<&mut Self>, ctx: &mut Context<'_>, buf: ReadBufCursor<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(ctx, buf)
+    }
+}
+
+impl<T> Write for HttpsStream<T>
+where
+    T: Read + Write + Unpin,
+{
+    fn poll_write(mut self: Pin<&mut Self>, ctx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(ctx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(ctx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(ctx)
+    }
+}
+
+impl<T> Connection for HttpsStream<T>
+where
+    T: Read + Write + Connection + Unpin,
+{
+    fn connected(&self) -> Connected {
+        let stream = self.get_ref();
+        let connected = stream.get_ref().inner().connected();
+
+        if stream.ssl().selected_alpn_protocol() == Some(b"h2") {
+            connected.negotiated_h2()
+        } else {
+            connected
+        }
+    }
+}
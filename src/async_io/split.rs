@@ -0,0 +1,104 @@
+use crate::async_io::{
+    runtime::{AsyncRead, AsyncWrite},
+    TlsStream,
+};
+use std::{
+    io,
+    marker::Unpin,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+/// The read half of a `TlsStream`, created by [`TlsStream::into_split`].
+#[derive(Debug)]
+pub struct ReadHalf<S>(Arc<Mutex<TlsStream<S>>>);
+
+/// The write half of a `TlsStream`, created by [`TlsStream::into_split`].
+#[derive(Debug)]
+pub struct WriteHalf<S>(Arc<Mutex<TlsStream<S>>>);
+
+pub(crate) fn split<S>(stream: TlsStream<S>) -> (ReadHalf<S>, WriteHalf<S>) {
+    let shared = Arc::new(Mutex::new(stream));
+    (ReadHalf(shared.clone()), WriteHalf(shared))
+}
+
+impl<S> ReadHalf<S> {
+    /// Joins the halves back into the original stream.
+    ///
+    /// Returns the halves as they were if they don't belong to the same stream.
+    pub fn reunite(self, other: WriteHalf<S>) -> Result<TlsStream<S>, (ReadHalf<S>, WriteHalf<S>)> {
+        if !Arc::ptr_eq(&self.0, &other.0) {
+            return Err((self, other));
+        }
+
+        drop(other);
+
+        match Arc::try_unwrap(self.0) {
+            Ok(stream) => Ok(stream.into_inner().unwrap()),
+            Err(_) => unreachable!("both halves are owned here"),
+        }
+    }
+}
+
+impl<S> AsyncRead for ReadHalf<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    #[cfg(any(feature = "io-async-std", feature = "io-futures"))]
+    fn poll_read(self: Pin<&mut Self>, ctx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream).poll_read(ctx, buf)
+    }
+
+    #[cfg(feature = "io-tokio")]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        ctx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream).poll_read(ctx, buf)
+    }
+}
+
+impl<S> AsyncWrite for WriteHalf<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, ctx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream).poll_write(ctx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        ctx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream).poll_write_vectored(ctx, bufs)
+    }
+
+    #[cfg(feature = "io-tokio")]
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream).poll_flush(ctx)
+    }
+
+    #[cfg(any(feature = "io-async-std", feature = "io-futures"))]
+    fn poll_close(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream).poll_close(ctx)
+    }
+
+    #[cfg(feature = "io-tokio")]
+    fn poll_shutdown(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream).poll_shutdown(ctx)
+    }
+}
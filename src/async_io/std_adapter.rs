@@ -36,7 +36,7 @@ impl<S> Read for StdAdapter<S>
 where
     S: AsyncRead + Unpin,
 {
-    #[cfg(feature = "io-async-std")]
+    #[cfg(any(feature = "io-async-std", feature = "io-futures"))]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self.with_context(|ctx, stream| stream.poll_read(ctx, buf)) {
             Poll::Ready(r) => r,
@@ -1,13 +1,20 @@
 use crate::{
     async_io::{
-        runtime::{AsyncRead, AsyncWrite},
+        runtime::{AsyncBufRead, AsyncRead, AsyncWrite},
+        split::{self, ReadHalf, WriteHalf},
         std_adapter::StdAdapter,
     },
     sync_io,
 };
+use openssl::{
+    hash::MessageDigest,
+    ssl::{ErrorCode, SslRef},
+};
 use std::{
+    cmp, future,
     io::{self, Read, Write},
     marker::Unpin,
+    mem,
     pin::Pin,
     ptr::null_mut,
     task::{Context, Poll},
@@ -22,61 +29,255 @@ use std::{
 /// and both the server and the client are ready for receiving and sending
 /// data. Bytes read from a `TlsStream` are decrypted from `S` and bytes written
 /// to a `TlsStream` are encrypted when passing through to `S`.
+///
+/// With `AsyncBufRead`, `poll_fill_buf` returns the plaintext OpenSSL already
+/// decrypted (`ssl().pending()`), peeked without consuming it, and `consume` drops
+/// the bytes from the session. Nothing is read ahead beyond the record OpenSSL
+/// holds, and encrypted bytes are only ever buffered inside OpenSSL, which hands
+/// every record to the transport as soon as it is written; flushing the stream
+/// flushes the transport.
+///
+/// With `io-tokio`, the stream implements Tokio's `AsyncRead` and `AsyncWrite`, and
+/// is `Unpin` and `Send` for an `Unpin + Send` transport, so it can be wrapped in
+/// `tokio_util::codec::Framed`.
 #[derive(Debug)]
-pub struct TlsStream<S>(sync_io::TlsStream<StdAdapter<S>>);
+pub struct TlsStream<S> {
+    stream: sync_io::TlsStream<StdAdapter<S>>,
+    peek_buf: PeekBuffer,
+}
 
 impl<S> TlsStream<S> {
     pub(crate) fn new(stream: sync_io::TlsStream<StdAdapter<S>>) -> Self {
-        Self(stream)
+        Self {
+            stream,
+            peek_buf: PeekBuffer::default(),
+        }
     }
     fn with_context<F, R>(&mut self, ctx: &mut Context<'_>, f: F) -> R
     where
         F: FnOnce(&mut sync_io::TlsStream<StdAdapter<S>>) -> R,
         StdAdapter<S>: Read + Write,
     {
-        self.0.get_mut().context = ctx as *mut _ as *mut ();
+        self.stream.get_mut().context = ctx as *mut _ as *mut ();
         let g = Guard(self);
-        f(&mut (g.0).0)
+        f(&mut g.0.stream)
     }
 
-    /// Returns a shared reference to the inner stream.
-    pub fn get_ref(&self) -> &S
+    /// Reads into `buf`, after dropping the bytes consumed with `AsyncBufRead`.
+    fn poll_read_plain(&mut self, ctx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>
     where
-        S: AsyncRead + AsyncWrite + Unpin,
+        StdAdapter<S>: Read + Write,
     {
-        &self.0.get_ref().inner
+        match self.poll_discard_consumed(ctx) {
+            Poll::Ready(Ok(())) => self.with_context(ctx, |s| cvt(s.read(buf))),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
     }
 
-    /// Returns a mutable reference to the inner stream.
-    pub fn get_mut(&mut self) -> &mut S
+    /// Drops the bytes consumed with `AsyncBufRead` from the session, and forgets the
+    /// peeked plaintext.
+    ///
+    /// The bytes are still pending inside OpenSSL, so reading them doesn't touch the
+    /// transport. They are read over the peeked copy, which is stale afterwards.
+    fn poll_discard_consumed(&mut self, ctx: &mut Context<'_>) -> Poll<io::Result<()>>
     where
-        S: AsyncRead + AsyncWrite + Unpin,
+        StdAdapter<S>: Read + Write,
+    {
+        self.peek_buf.filled = self.peek_buf.pos;
+
+        while self.peek_buf.pos > 0 {
+            let consumed = self.peek_buf.pos;
+            let mut buf = mem::take(&mut self.peek_buf.buf);
+            let res = self.with_context(ctx, |s| cvt(s.read(&mut buf[..consumed])));
+            self.peek_buf.buf = buf;
+
+            match res {
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(len)) => self.peek_buf.pos -= len,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        self.peek_buf.pos = 0;
+        self.peek_buf.filled = 0;
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Sends our close_notify, without waiting for the peer to answer with its own.
+    fn poll_tls_shutdown(&mut self, ctx: &mut Context<'_>) -> Poll<io::Result<()>>
+    where
+        StdAdapter<S>: Read + Write,
     {
-        &mut self.0.get_mut().inner
+        self.with_context(ctx, |s| match s.0.shutdown() {
+            Ok(_) => Poll::Ready(Ok(())),
+            Err(ref e) if e.code() == ErrorCode::ZERO_RETURN => Poll::Ready(Ok(())),
+            // The peer closed the transport before our close_notify could be sent.
+            Err(ref e) if e.code() == ErrorCode::SYSCALL && e.io_error().is_none() => Poll::Ready(Ok(())),
+            Err(e) => cvt(Err(sync_io::into_io_error(e))),
+        })
     }
 
-    /// Returns the number of bytes that can be read without resulting in any network calls.
-    pub fn buffered_read_size(&self) -> crate::Result<usize>
+    /// Performs the bidirectional TLS shutdown: sends our close_notify and waits for the
+    /// peer to answer with its own.
+    ///
+    /// Closing the stream with `AsyncWrite` only sends our close_notify. Read all data the
+    /// peer sends before its close_notify first: receiving application data while waiting
+    /// fails with an error of kind `InvalidData`. A peer which never answers stalls this
+    /// forever, so wrap it in a timeout of the runtime where the peer isn't trusted.
+    pub async fn shutdown_bidirectional(&mut self) -> io::Result<()>
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
-        self.0.buffered_read_size()
+        future::poll_fn(|ctx| self.poll_tls_shutdown(ctx)).await?;
+
+        let mut buf = [0; 1];
+        match future::poll_fn(|ctx| self.poll_read_plain(ctx, &mut buf)).await? {
+            0 => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "application data received after close_notify",
+            )),
+        }
     }
 
-    /// Returns the peer's leaf certificate, if available.
-    pub fn peer_certificate(&self) -> crate::Result<Option<crate::Certificate>>
+    /// Returns a shared reference to the inner stream.
+    pub fn get_ref(&self) -> &S
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
-        self.0.peer_certificate()
+        &self.stream.get_ref().inner
     }
 
-    /// Returns the tls-server-end-point channel binding data as defined in [RFC 5929](https://tools.ietf.org/html/rfc5929).
-    pub fn tls_server_end_point(&self) -> crate::Result<Option<Vec<u8>>>
+    /// Returns a mutable reference to the inner stream.
+    pub fn get_mut(&mut self) -> &mut S
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
-        self.0.tls_server_end_point()
+        &mut self.stream.get_mut().inner
+    }
+
+    /// Splits the stream into owned read and write halves, which can be moved to
+    /// different tasks.
+    ///
+    /// A TLS session can't be read and written independently, so the halves share the
+    /// stream behind a mutex. Every read or write takes the lock for the duration of a
+    /// single poll, which makes the halves slightly slower than the stream itself, and
+    /// reads and writes never run in parallel. The halves can be joined back with
+    /// [`ReadHalf::reunite`].
+    pub fn into_split(self) -> (ReadHalf<S>, WriteHalf<S>) {
+        split::split(self)
+    }
+
+    /// Returns the underlying OpenSSL connection, see `sync_io::TlsStream::ssl`.
+    pub fn ssl(&self) -> &SslRef {
+        self.stream.ssl()
+    }
+
+    /// Returns the number of bytes that can be read without resulting in any network calls.
+    ///
+    /// Counts the decrypted bytes pending inside OpenSSL, including the plaintext returned
+    /// by `AsyncBufRead::poll_fill_buf` but not yet consumed, so a read of up to this size
+    /// completes without polling the underlying stream.
+    pub fn buffered_read_size(&self) -> crate::Result<usize> {
+        Ok(self.stream.buffered_read_size()? - self.peek_buf.pos)
+    }
+
+    /// Returns the number of application data bytes read from the TLS session so far. With
+    /// `AsyncBufRead`, only consumed bytes are counted.
+    pub fn bytes_read(&self) -> u64 {
+        self.stream.bytes_read() + self.peek_buf.pos as u64
+    }
+
+    /// Returns the number of application data bytes written to the stream so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.stream.bytes_written()
+    }
+
+    /// Returns the peer's leaf certificate, if available.
+    pub fn peer_certificate(&self) -> crate::Result<Option<crate::Certificate>> {
+        self.stream.peer_certificate()
+    }
+
+    /// Returns whether the current time lies within the validity period of the peer's leaf
+    /// certificate, or `None` if the peer sent no certificate.
+    pub fn peer_certificate_valid_now(&self) -> crate::Result<Option<bool>> {
+        self.stream.peer_certificate_valid_now()
+    }
+
+    /// Returns the peer's certificate chain as built and verified by OpenSSL, from the leaf
+    /// certificate to the trust anchor, if available.
+    ///
+    /// Requires OpenSSL 1.1.0 or newer.
+    #[cfg(have_ossl110)]
+    pub fn verified_chain(&self) -> crate::Result<Option<Vec<crate::Certificate>>> {
+        self.stream.verified_chain()
+    }
+
+    /// Returns the fingerprint of the peer's leaf certificate, if available.
+    pub fn peer_certificate_fingerprint(&self, digest: MessageDigest) -> crate::Result<Option<Vec<u8>>> {
+        self.stream.peer_certificate_fingerprint(digest)
+    }
+
+    /// Returns the DER-encoded OCSP response stapled by the server, if available.
+    pub fn ocsp_response(&self) -> crate::Result<Option<Vec<u8>>> {
+        self.stream.ocsp_response()
+    }
+
+    /// Returns the host name requested by the client with SNI, or `None` if it sent none.
+    pub fn servername(&self) -> Option<String> {
+        self.stream.servername()
+    }
+
+    /// Returns the name of the group negotiated for the key exchange, such as `x25519`.
+    /// Requires OpenSSL 3.0 or newer.
+    pub fn negotiated_group(&self) -> Option<String> {
+        self.stream.negotiated_group()
+    }
+
+    /// Returns `true` if this is the server side of the connection.
+    pub fn is_server(&self) -> bool {
+        self.stream.is_server()
+    }
+
+    /// Returns `true` if this is the client side of the connection.
+    pub fn is_client(&self) -> bool {
+        self.stream.is_client()
+    }
+
+    /// Returns `true` if the connection resumed a previously negotiated session.
+    pub fn session_reused(&self) -> bool {
+        self.stream.session_reused()
+    }
+
+    /// Returns the negotiated protocol, cipher suite and ALPN protocol, together with the
+    /// peer's certificate and whether the session was resumed.
+    pub fn connection_info(&self) -> crate::Result<crate::ConnectionInfo> {
+        self.stream.connection_info()
+    }
+
+    /// Requests a certificate from the client of an established TLS 1.3 connection.
+    ///
+    /// The request is sent with the next write to the stream, see
+    /// `sync_io::TlsStream::request_client_auth`.
+    #[cfg(have_ossl111)]
+    pub fn request_client_auth(&mut self) -> crate::Result<()> {
+        self.stream.request_client_auth()
+    }
+
+    /// Returns the master secret of the session. Only meant for test harnesses, see
+    /// `sync_io::TlsStream::master_key`.
+    #[cfg(all(feature = "insecure-export-secrets", have_ossl110))]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "insecure-export-secrets")))]
+    pub fn master_key(&self) -> Vec<u8> {
+        self.stream.master_key()
+    }
+
+    /// Returns the tls-server-end-point channel binding data as defined in [RFC 5929](https://tools.ietf.org/html/rfc5929).
+    pub fn tls_server_end_point(&self) -> crate::Result<Option<Vec<u8>>> {
+        self.stream.tls_server_end_point()
     }
 }
 
@@ -84,9 +285,9 @@ impl<S> AsyncRead for TlsStream<S>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    #[cfg(feature = "io-async-std")]
+    #[cfg(any(feature = "io-async-std", feature = "io-futures"))]
     fn poll_read(mut self: Pin<&mut Self>, ctx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
-        self.with_context(ctx, |s| cvt(s.read(buf)))
+        self.poll_read_plain(ctx, buf)
     }
 
     #[cfg(feature = "io-tokio")]
@@ -95,7 +296,7 @@ where
         ctx: &mut Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        match self.with_context(ctx, |s| cvt(s.read(buf.initialize_unfilled()))) {
+        match self.poll_read_plain(ctx, buf.initialize_unfilled()) {
             Poll::Ready(Ok(len)) => {
                 buf.advance(len);
                 Poll::Ready(Ok(()))
@@ -106,6 +307,53 @@ where
     }
 }
 
+impl<S> AsyncBufRead for TlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+
+        if this.peek_buf.available().is_empty() {
+            match this.poll_discard_consumed(ctx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+
+            // Have OpenSSL decrypt the next record if it has no plaintext pending.
+            if this.stream.ssl().pending() == 0 {
+                let mut byte = [0; 1];
+
+                match this.with_context(ctx, |s| cvt(s.peek(&mut byte))) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Ok(&[])),
+                    Poll::Ready(Ok(_)) => {}
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            let mut buf = mem::take(&mut this.peek_buf.buf);
+            buf.resize(cmp::max(this.stream.ssl().pending(), 1), 0);
+
+            let res = this.with_context(ctx, |s| cvt(s.peek(&mut buf)));
+            this.peek_buf.buf = buf;
+
+            match res {
+                Poll::Ready(Ok(len)) => this.peek_buf.filled = len,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        Poll::Ready(Ok(this.peek_buf.available()))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().peek_buf.consume(amt);
+    }
+}
+
 impl<S> AsyncWrite for TlsStream<S>
 where
     S: AsyncRead + AsyncWrite + Unpin,
@@ -114,27 +362,38 @@ where
         self.with_context(ctx, |s| cvt(s.write(buf)))
     }
 
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.with_context(ctx, |s| cvt(s.write_vectored(bufs)))
+    }
+
+    #[cfg(feature = "io-tokio")]
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
     fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
         self.with_context(ctx, |s| cvt(s.flush()))
     }
 
-    #[cfg(feature = "io-async-std")]
-    #[cfg_attr(feature = "docs", doc(cfg(feature = "io-async-std")))]
+    #[cfg(any(feature = "io-async-std", feature = "io-futures"))]
+    #[cfg_attr(feature = "docs", doc(cfg(any(feature = "io-async-std", feature = "io-futures"))))]
     fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self.with_context(ctx, |s| s.shutdown()) {
-            Ok(()) => Poll::Ready(Ok(())),
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
-            Err(e) => Poll::Ready(Err(e)),
+        match self.poll_tls_shutdown(ctx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut self.stream.get_mut().inner).poll_close(ctx),
+            res => res,
         }
     }
 
     #[cfg(feature = "io-tokio")]
     #[cfg_attr(feature = "docs", doc(cfg(feature = "io-tokio")))]
     fn poll_shutdown(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self.with_context(ctx, |s| s.shutdown()) {
-            Ok(()) => Poll::Ready(Ok(())),
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
-            Err(e) => Poll::Ready(Err(e)),
+        match self.poll_tls_shutdown(ctx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut self.stream.get_mut().inner).poll_shutdown(ctx),
+            res => res,
         }
     }
 }
@@ -148,7 +407,28 @@ where
     StdAdapter<S>: Read + Write,
 {
     fn drop(&mut self) {
-        (self.0).0.get_mut().context = null_mut();
+        self.0.stream.get_mut().context = null_mut();
+    }
+}
+
+/// A copy of the front of the plaintext pending inside OpenSSL, for `AsyncBufRead`.
+///
+/// The bytes before `pos` were consumed, but are only dropped from the session by the
+/// next poll, as reading them needs a context.
+#[derive(Debug, Default)]
+struct PeekBuffer {
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+}
+
+impl PeekBuffer {
+    fn available(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.filled);
     }
 }
 
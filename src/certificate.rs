@@ -1,4 +1,20 @@
-use openssl::x509::X509;
+use openssl::{
+    error::ErrorStack,
+    hash::MessageDigest,
+    stack::Stack,
+    x509::{
+        store::{X509StoreBuilder, X509StoreBuilderRef},
+        X509NameRef, X509StoreContext, X509,
+    },
+};
+
+use std::{
+    hash::{Hash, Hasher},
+    os::raw::c_int,
+};
+
+#[cfg(feature = "serde")]
+use openssl::base64;
 
 /// An X509 certificate.
 #[derive(Clone, Debug)]
@@ -22,6 +38,117 @@ impl Certificate {
         let der = self.0.to_der()?;
         Ok(der)
     }
+
+    /// Returns the fingerprint of the certificate, the digest of its DER encoding.
+    ///
+    /// ```rust
+    /// use opentls::{Certificate, MessageDigest};
+    ///
+    /// let cert = Certificate::from_der(include_bytes!("../tests/cert.der")).unwrap();
+    /// let fingerprint = cert.fingerprint(MessageDigest::sha256()).unwrap();
+    /// assert_eq!(fingerprint.len(), 32);
+    /// ```
+    pub fn fingerprint(&self, digest: MessageDigest) -> crate::Result<Vec<u8>> {
+        Ok(self.0.digest(digest)?.to_vec())
+    }
+
+    /// Returns the components of the subject's distinguished name as pairs of the short name
+    /// of the attribute, e.g. `CN`, `O` or `OU`, and its value, in the order of the certificate.
+    ///
+    /// Attributes without a short name known to OpenSSL are named by their OID.
+    ///
+    /// ```rust
+    /// use opentls::Certificate;
+    ///
+    /// let cert = Certificate::from_der(include_bytes!("../tests/cert.der")).unwrap();
+    /// let entries = cert.subject_entries().unwrap();
+    /// assert!(entries.contains(&("CN".to_string(), "foobar.com".to_string())));
+    /// ```
+    pub fn subject_entries(&self) -> crate::Result<Vec<(String, String)>> {
+        name_entries(self.0.subject_name())
+    }
+
+    /// Returns the components of the issuer's distinguished name, like `subject_entries`.
+    pub fn issuer_entries(&self) -> crate::Result<Vec<(String, String)>> {
+        name_entries(self.0.issuer_name())
+    }
+
+    /// Verifies the certificate against the given roots, without a handshake.
+    ///
+    /// The `intermediates` are used to build the chain from the certificate to one of the
+    /// `roots`, they are not trusted themselves. The system roots are not used. Returns
+    /// `false` if the certificate is invalid, e.g. because it expired or because the chain
+    /// to a root can't be completed from the given intermediates.
+    pub fn verify_chain(&self, intermediates: &[Certificate], roots: &[Certificate]) -> crate::Result<bool> {
+        let mut store = X509StoreBuilder::new()?;
+
+        for root in roots {
+            store.add_cert(root.0.clone())?;
+        }
+
+        let store = store.build();
+        let mut chain = Stack::new()?;
+
+        for cert in intermediates {
+            chain.push(cert.0.clone())?;
+        }
+
+        let mut context = X509StoreContext::new()?;
+        let valid = context.init(&store, &self.0, &chain, |c| c.verify_cert())?;
+
+        Ok(valid)
+    }
+
+    /// Adds the certificate to `store` as a trusted root.
+    ///
+    /// A root already in the store is skipped, as OpenSSL older than 1.1.1 refuses to add it
+    /// twice, which is harmless. Any other error is returned.
+    pub(crate) fn add_to_store(&self, store: &mut X509StoreBuilderRef) -> Result<(), ErrorStack> {
+        // `ERR_LIB_X509` and `X509_R_CERT_ALREADY_IN_HASH_TABLE`, not exposed by `openssl-sys`.
+        const ERR_LIB_X509: c_int = 11;
+        const X509_R_CERT_ALREADY_IN_HASH_TABLE: c_int = 101;
+
+        match store.add_cert(self.0.clone()) {
+            Err(err)
+                if !err.errors().is_empty()
+                    && err.errors().iter().all(|e| {
+                        e.library_code() == ERR_LIB_X509 && e.reason_code() == X509_R_CERT_ALREADY_IN_HASH_TABLE
+                    }) =>
+            {
+                Ok(())
+            }
+            res => res,
+        }
+    }
+}
+
+fn name_entries(name: &X509NameRef) -> crate::Result<Vec<(String, String)>> {
+    name.entries()
+        .map(|entry| {
+            let object = entry.object();
+            let key = match object.nid().short_name() {
+                Ok(name) => name.to_string(),
+                Err(_) => object.to_string(),
+            };
+
+            Ok((key, entry.data().to_string()?))
+        })
+        .collect()
+}
+
+/// Certificates are equal if their DER encodings are.
+impl PartialEq for Certificate {
+    fn eq(&self, other: &Certificate) -> bool {
+        self.0.to_der().ok() == other.0.to_der().ok()
+    }
+}
+
+impl Eq for Certificate {}
+
+impl Hash for Certificate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_der().ok().hash(state);
+    }
 }
 
 impl From<X509> for Certificate {
@@ -29,3 +156,93 @@ impl From<X509> for Certificate {
         Self(inner)
     }
 }
+
+#[cfg(feature = "serde")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "serde")))]
+impl serde::Serialize for Certificate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let der = self.to_der().map_err(serde::ser::Error::custom)?;
+
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64::encode_block(&der))
+        } else {
+            serializer.serialize_bytes(&der)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(feature = "docs", doc(cfg(feature = "serde")))]
+impl<'de> serde::Deserialize<'de> for Certificate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(CertificateVisitor)
+        } else {
+            deserializer.deserialize_bytes(CertificateVisitor)
+        }
+    }
+}
+
+/// Parses either base64-encoded or raw DER.
+#[cfg(feature = "serde")]
+struct CertificateVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for CertificateVisitor {
+    type Value = Certificate;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("a DER-encoded certificate")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Certificate, E> {
+        let der = base64::decode_block(v).map_err(E::custom)?;
+        Certificate::from_der(&der).map_err(E::custom)
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Certificate, E> {
+        Certificate::from_der(v).map_err(E::custom)
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Certificate, A::Error> {
+        let mut der = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+
+        while let Some(byte) = seq.next_element()? {
+            der.push(byte);
+        }
+
+        self.visit_bytes(&der)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::Certificate;
+    use openssl::base64;
+    use serde::{de::value, Deserialize};
+
+    const DER: &[u8] = include_bytes!("../tests/cert.der");
+
+    #[test]
+    fn deserialize_base64() {
+        let encoded = base64::encode_block(DER);
+        let de = value::StrDeserializer::<value::Error>::new(&encoded);
+        let cert = Certificate::deserialize(de).unwrap();
+
+        assert_eq!(cert.to_der().unwrap(), DER);
+    }
+
+    #[test]
+    fn deserialize_bytes() {
+        let de = value::BytesDeserializer::<value::Error>::new(DER);
+        let cert = Certificate::deserialize(de).unwrap();
+
+        assert_eq!(cert.to_der().unwrap(), DER);
+    }
+
+    #[test]
+    fn deserialize_invalid() {
+        let de = value::StrDeserializer::<value::Error>::new("bm90IGEgY2VydA==");
+        assert!(Certificate::deserialize(de).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use crate::Certificate;
+
+/// The parameters negotiated for a TLS session.
+///
+/// Returned by `TlsStream::connection_info`, bundling what is otherwise available from
+/// several accessors, e.g. for logging a connection.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    /// The protocol version, e.g. `TLSv1.3`.
+    pub protocol: String,
+    /// The name of the cipher suite, e.g. `TLS_AES_256_GCM_SHA384`.
+    pub cipher: Option<String>,
+    /// The protocol selected with ALPN, if any.
+    pub alpn_protocol: Option<Vec<u8>>,
+    /// Whether the connection resumed a previously negotiated session.
+    pub session_reused: bool,
+    /// The peer's leaf certificate, if available.
+    pub peer_certificate: Option<Certificate>,
+}
@@ -0,0 +1,316 @@
+//! DTLS over datagram sockets.
+//!
+//! DTLS provides the guarantees of TLS on top of an unreliable datagram
+//! transport, such as UDP. The connections established here are regular
+//! [`TlsStream`]s over a [`UdpChannel`], so reading and writing works exactly
+//! like with TLS, with every write sent as a single datagram.
+//!
+//! # Limitations
+//!
+//! * The acceptor doesn't perform the stateless cookie exchange, so it must only
+//!   be given sockets already connected to a single peer.
+//! * OpenSSL can't query the path MTU of the socket, so records are sized for a
+//!   fixed MTU, see [`DtlsConnectorBuilder::mtu`].
+//! * Lost handshake packets are only retransmitted when the handshake is resumed
+//!   after the retransmission timeout, which requires a read timeout or a
+//!   nonblocking socket.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use opentls::dtls::{DtlsConnector, UdpChannel};
+//! use std::io::Write;
+//! use std::net::UdpSocket;
+//!
+//! let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+//! socket.connect("example.com:4433").unwrap();
+//!
+//! let connector = DtlsConnector::new().unwrap();
+//! let mut stream = connector.connect("example.com", UdpChannel::new(socket)).unwrap();
+//! stream.write_all(b"hello").unwrap();
+//! ```
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::UdpSocket,
+};
+
+use openssl::{
+    ssl::{Ssl, SslAcceptor, SslConnector, SslMethod, SslOptions, SslVerifyMode},
+    x509::store::X509StoreBuilder,
+};
+
+use crate::{sync_io::TlsStream, Certificate, HandshakeError, Identity};
+
+/// The default MTU, small enough to avoid IP fragmentation on practically every path.
+const DEFAULT_MTU: u32 = 1200;
+
+/// A connected `UdpSocket`, used as the transport of a DTLS connection.
+///
+/// Every write is sent as one datagram to the connected peer, and every read
+/// receives one datagram.
+#[derive(Debug)]
+pub struct UdpChannel(UdpSocket);
+
+impl UdpChannel {
+    /// Wraps a socket, which must already be connected to the peer.
+    pub fn new(socket: UdpSocket) -> Self {
+        Self(socket)
+    }
+
+    /// Returns a shared reference to the socket.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.0
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> UdpSocket {
+        self.0
+    }
+}
+
+impl From<UdpSocket> for UdpChannel {
+    fn from(socket: UdpSocket) -> Self {
+        Self::new(socket)
+    }
+}
+
+impl Read for UdpChannel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.recv(buf)
+    }
+}
+
+impl Write for UdpChannel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A builder for client-side DTLS connections.
+#[derive(Clone)]
+pub struct DtlsConnector {
+    connector: SslConnector,
+    mtu: u32,
+    accept_invalid_hostnames: bool,
+    accept_invalid_certs: bool,
+}
+
+impl fmt::Debug for DtlsConnector {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("DtlsConnector")
+            .field("mtu", &self.mtu)
+            .field("accept_invalid_hostnames", &self.accept_invalid_hostnames)
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .finish()
+    }
+}
+
+impl DtlsConnector {
+    /// Returns a new connector with default settings.
+    pub fn new() -> crate::Result<Self> {
+        Self::builder().build()
+    }
+
+    /// Returns a new builder for a `DtlsConnector`.
+    pub fn builder() -> DtlsConnectorBuilder {
+        DtlsConnectorBuilder {
+            identity: None,
+            root_certificates: vec![],
+            disable_built_in_roots: false,
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            mtu: DEFAULT_MTU,
+        }
+    }
+
+    /// Initiates a DTLS handshake.
+    ///
+    /// The provided domain will be used for both SNI and certificate hostname
+    /// validation.
+    pub fn connect<S>(&self, domain: &str, stream: S) -> Result<TlsStream<S>, HandshakeError<S>>
+    where
+        S: io::Read + io::Write,
+    {
+        let mut ssl = self
+            .connector
+            .configure()?
+            .verify_hostname(!self.accept_invalid_hostnames);
+
+        if self.accept_invalid_certs {
+            ssl.set_verify(SslVerifyMode::NONE);
+        }
+
+        ssl.set_mtu(self.mtu)?;
+
+        Ok(TlsStream::new(ssl.connect(domain, stream)?))
+    }
+}
+
+/// A builder for `DtlsConnector`s.
+#[derive(Clone, Debug)]
+pub struct DtlsConnectorBuilder {
+    identity: Option<Identity>,
+    root_certificates: Vec<Certificate>,
+    disable_built_in_roots: bool,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    mtu: u32,
+}
+
+impl DtlsConnectorBuilder {
+    /// Sets the identity to be used for client certificate authentication.
+    pub fn identity(&mut self, identity: Identity) -> &mut DtlsConnectorBuilder {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Adds a certificate to the set of roots that the connector will trust.
+    ///
+    /// The connector will use the system's trust root by default. This method can be used to add
+    /// to that set when communicating with servers not trusted by the system.
+    ///
+    /// Defaults to an empty set.
+    pub fn add_root_certificate(&mut self, cert: Certificate) -> &mut DtlsConnectorBuilder {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    /// Controls the use of built-in system certificates during certificate validation.
+    ///
+    /// Defaults to `false` -- built-in system certs will be used.
+    pub fn disable_built_in_roots(&mut self, disable: bool) -> &mut DtlsConnectorBuilder {
+        self.disable_built_in_roots = disable;
+        self
+    }
+
+    /// Controls the use of certificate validation.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Warning
+    ///
+    /// You should think very carefully before using this method. If invalid certificates are trusted, *any*
+    /// certificate for *any* site will be trusted for use. This includes expired certificates. This introduces
+    /// significant vulnerabilities, and should only be used as a last resort.
+    pub fn danger_accept_invalid_certs(&mut self, accept_invalid_certs: bool) -> &mut DtlsConnectorBuilder {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// Controls the use of hostname verification.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Warning
+    ///
+    /// You should think very carefully before using this method. If invalid hostnames are trusted, *any* valid
+    /// certificate for *any* site will be trusted for use. This introduces significant vulnerabilities, and should
+    /// only be used as a last resort.
+    pub fn danger_accept_invalid_hostnames(&mut self, accept_invalid_hostnames: bool) -> &mut DtlsConnectorBuilder {
+        self.accept_invalid_hostnames = accept_invalid_hostnames;
+        self
+    }
+
+    /// Sets the maximum size of the datagrams sent, excluding the IP and UDP headers.
+    ///
+    /// Defaults to 1200 bytes.
+    pub fn mtu(&mut self, mtu: u32) -> &mut DtlsConnectorBuilder {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Creates a new `DtlsConnector`.
+    pub fn build(&self) -> crate::Result<DtlsConnector> {
+        crate::init_openssl_env();
+
+        let mut connector = SslConnector::builder(SslMethod::dtls())?;
+        connector.set_options(SslOptions::NO_QUERY_MTU);
+
+        if let Some(ref identity) = self.identity {
+            connector.set_certificate(&identity.cert)?;
+            connector.set_private_key(&identity.pkey)?;
+
+            for cert in identity.chain.iter().rev() {
+                connector.add_extra_chain_cert(cert.to_owned())?;
+            }
+        }
+
+        if self.disable_built_in_roots {
+            connector.set_cert_store(X509StoreBuilder::new()?.build());
+        }
+
+        for cert in &self.root_certificates {
+            cert.add_to_store(connector.cert_store_mut())?;
+        }
+
+        Ok(DtlsConnector {
+            connector: connector.build(),
+            mtu: self.mtu,
+            accept_invalid_hostnames: self.accept_invalid_hostnames,
+            accept_invalid_certs: self.accept_invalid_certs,
+        })
+    }
+}
+
+/// A builder for server-side DTLS connections.
+#[derive(Clone)]
+pub struct DtlsAcceptor {
+    acceptor: SslAcceptor,
+    mtu: u32,
+}
+
+impl fmt::Debug for DtlsAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DtlsAcceptor").field("mtu", &self.mtu).finish()
+    }
+}
+
+impl DtlsAcceptor {
+    /// Creates an acceptor with default settings.
+    ///
+    /// The identity acts as the server's private key/certificate chain.
+    pub fn new(identity: Identity) -> crate::Result<DtlsAcceptor> {
+        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::dtls())?;
+        acceptor.set_options(SslOptions::NO_QUERY_MTU);
+
+        acceptor.set_private_key(&identity.pkey)?;
+        acceptor.set_certificate(&identity.cert)?;
+
+        for cert in identity.chain.iter().rev() {
+            acceptor.add_extra_chain_cert(cert.to_owned())?;
+        }
+
+        Ok(DtlsAcceptor {
+            acceptor: acceptor.build(),
+            mtu: DEFAULT_MTU,
+        })
+    }
+
+    /// Sets the maximum size of the datagrams sent, excluding the IP and UDP headers.
+    ///
+    /// Defaults to 1200 bytes.
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Initiates a DTLS handshake.
+    ///
+    /// The stream must only carry the datagrams of a single peer, e.g. a
+    /// `UdpChannel` over a socket connected to the peer.
+    pub fn accept<S>(&self, stream: S) -> Result<TlsStream<S>, HandshakeError<S>>
+    where
+        S: io::Read + io::Write,
+    {
+        let mut ssl = Ssl::new(self.acceptor.context())?;
+        ssl.set_mtu(self.mtu)?;
+
+        Ok(TlsStream::new(ssl.accept(stream)?))
+    }
+}
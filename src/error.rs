@@ -1,8 +1,12 @@
+use crate::sync_io::MidHandshakeTlsStream;
 use openssl::{error::ErrorStack, ssl, x509::X509VerifyResult};
-use std::{error, fmt, io};
+use openssl_sys as ffi;
+use std::{error, fmt, io, os::raw::c_int};
 
 /// An error returned from the TLS implementation.
-#[derive(Debug)]
+///
+/// The `Debug` output lists every error of the OpenSSL error stack with its code, library,
+/// function and reason, such as `dh key too small`.
 pub enum Error {
     /// Collection of [`Error`]s from OpenSSL.
     Normal(ErrorStack),
@@ -10,6 +14,78 @@ pub enum Error {
     Ssl(ssl::Error, X509VerifyResult),
     /// An I/O error.
     Io(io::Error),
+    /// A renegotiation of the connection was refused, either by us or the peer.
+    ///
+    /// Renegotiation with peers not supporting secure renegotiation (RFC 5746) is refused
+    /// unless allowed with `TlsConnectorBuilder::allow_legacy_renegotiation`, and every
+    /// renegotiation is refused with `disable_renegotiation`. Reads and writes return it
+    /// wrapped in an I/O error of kind `InvalidData`.
+    Renegotiation(ssl::Error),
+    /// The HTTP proxy refused to open a tunnel, with the status code of its response, e.g.
+    /// 407 if it requires authentication.
+    Proxy(u16),
+}
+
+impl Error {
+    /// Returns `true` if the error was caused by the underlying I/O stream.
+    pub fn is_io(&self) -> bool {
+        match *self {
+            Error::Io(_) => true,
+            Error::Ssl(ref e, _) => e.io_error().is_some(),
+            Error::Normal(_) | Error::Renegotiation(_) | Error::Proxy(_) => false,
+        }
+    }
+
+    /// Returns the result of the certificate verification, if it failed.
+    pub fn verify_result(&self) -> Option<X509VerifyResult> {
+        match *self {
+            Error::Ssl(_, X509VerifyResult::OK) => None,
+            Error::Ssl(_, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the reason of the failed certificate verification, if it failed.
+    pub fn verify_error(&self) -> Option<VerifyError> {
+        self.verify_result().map(VerifyError::from)
+    }
+
+    /// Returns `true` if the error was caused by a failed certificate verification.
+    pub fn is_verify_error(&self) -> bool {
+        self.verify_result().is_some()
+    }
+
+    /// Returns `true` if parsing a PKCS #12 archive with `Identity::from_pkcs12` failed because
+    /// of a wrong password, as opposed to an archive which isn't valid.
+    ///
+    /// The password is checked against the MAC of the archive, so an archive damaged in a way
+    /// that only breaks the MAC is reported as a wrong password as well.
+    pub fn is_wrong_password(&self) -> bool {
+        // `ERR_LIB_PKCS12` and `PKCS12_R_MAC_VERIFY_FAILURE`, not exposed by `openssl-sys`.
+        const ERR_LIB_PKCS12: c_int = 35;
+        const PKCS12_R_MAC_VERIFY_FAILURE: c_int = 113;
+
+        match *self {
+            Error::Normal(ref stack) => stack
+                .errors()
+                .iter()
+                .any(|e| e.library_code() == ERR_LIB_PKCS12 && e.reason_code() == PKCS12_R_MAC_VERIFY_FAILURE),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if a renegotiation of the connection was refused.
+    ///
+    /// Also looks into I/O errors, since reads and writes return the error wrapped in one.
+    pub fn is_renegotiation_error(&self) -> bool {
+        match *self {
+            Error::Renegotiation(_) => true,
+            Error::Io(ref e) => e
+                .get_ref()
+                .is_some_and(|e| matches!(e.downcast_ref::<Error>(), Some(Error::Renegotiation(_)))),
+            _ => false,
+        }
+    }
 }
 
 impl error::Error for Error {
@@ -18,10 +94,84 @@ impl error::Error for Error {
             Error::Normal(ref e) => error::Error::source(e),
             Error::Ssl(ref e, _) => error::Error::source(e),
             Error::Io(ref e) => error::Error::source(e),
+            Error::Renegotiation(ref e) => error::Error::source(e),
+            Error::Proxy(_) => None,
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Normal(ref e) => fmt.debug_tuple("Normal").field(&StackDebug(e)).finish(),
+            Error::Ssl(ref e, v) => fmt
+                .debug_struct("Ssl")
+                .field("code", &CodeDebug(e.code()))
+                .field("errors", &e.ssl_error().map(StackDebug))
+                .field("io", &e.io_error())
+                .field("verify_result", &v)
+                .finish(),
+            Error::Io(ref e) => fmt.debug_tuple("Io").field(e).finish(),
+            Error::Renegotiation(ref e) => fmt
+                .debug_struct("Renegotiation")
+                .field("code", &CodeDebug(e.code()))
+                .field("errors", &e.ssl_error().map(StackDebug))
+                .finish(),
+            Error::Proxy(status) => fmt.debug_tuple("Proxy").field(&status).finish(),
         }
     }
 }
 
+/// Names the result code of an SSL operation.
+struct CodeDebug(ssl::ErrorCode);
+
+impl fmt::Debug for CodeDebug {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            ssl::ErrorCode::ZERO_RETURN => "ZERO_RETURN",
+            ssl::ErrorCode::WANT_READ => "WANT_READ",
+            ssl::ErrorCode::WANT_WRITE => "WANT_WRITE",
+            ssl::ErrorCode::SYSCALL => "SYSCALL",
+            ssl::ErrorCode::SSL => "SSL",
+            code => return write!(fmt, "{}", code.as_raw()),
+        };
+
+        fmt.write_str(name)
+    }
+}
+
+/// Lists the errors of the stack, from the oldest one.
+struct StackDebug<'a>(&'a ErrorStack);
+
+impl fmt::Debug for StackDebug<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_list()
+            .entries(self.0.errors().iter().map(ErrorDebug))
+            .finish()
+    }
+}
+
+struct ErrorDebug<'a>(&'a openssl::error::Error);
+
+impl fmt::Debug for ErrorDebug<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let e = self.0;
+        let mut debug = fmt.debug_struct("OpenSslError");
+        debug
+            .field("code", &format_args!("{:08X}", e.code()))
+            .field("library", &e.library().unwrap_or("unknown"))
+            .field("function", &e.function().unwrap_or("unknown"))
+            .field("reason", &e.reason().unwrap_or("unknown"))
+            .field("location", &format_args!("{}:{}", e.file(), e.line()));
+
+        if let Some(data) = e.data() {
+            debug.field("data", &data);
+        }
+
+        debug.finish()
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -29,6 +179,8 @@ impl fmt::Display for Error {
             Error::Io(ref e) => fmt::Display::fmt(e, fmt),
             Error::Ssl(ref e, X509VerifyResult::OK) => fmt::Display::fmt(e, fmt),
             Error::Ssl(ref e, v) => write!(fmt, "{} ({})", e, v),
+            Error::Renegotiation(ref e) => write!(fmt, "renegotiation refused: {}", e),
+            Error::Proxy(status) => write!(fmt, "proxy refused to open a tunnel with status {}", status),
         }
     }
 }
@@ -45,6 +197,61 @@ impl From<io::Error> for Error {
     }
 }
 
+/// The common reasons of a failed certificate verification.
+///
+/// Converted from the `X509VerifyResult` of OpenSSL, so failures can be matched on without
+/// comparing raw codes or messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerifyError {
+    /// A certificate of the chain has expired.
+    Expired,
+    /// A certificate of the chain is not valid yet.
+    NotYetValid,
+    /// The chain ends in a self-signed certificate which is not trusted.
+    SelfSigned,
+    /// The issuer of a certificate of the chain is unknown.
+    UnknownCa,
+    /// The certificate doesn't match the host or IP address connected to.
+    HostnameMismatch,
+    /// A certificate of the chain was revoked.
+    Revoked,
+    /// Any other failure, with the raw OpenSSL code, e.g. `X509_V_ERR_CERT_CHAIN_TOO_LONG`.
+    Other(i32),
+}
+
+impl From<X509VerifyResult> for VerifyError {
+    fn from(result: X509VerifyResult) -> VerifyError {
+        match result.as_raw() {
+            ffi::X509_V_ERR_CERT_HAS_EXPIRED => VerifyError::Expired,
+            ffi::X509_V_ERR_CERT_NOT_YET_VALID => VerifyError::NotYetValid,
+            ffi::X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT | ffi::X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN => {
+                VerifyError::SelfSigned
+            }
+            ffi::X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT
+            | ffi::X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY
+            | ffi::X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE => VerifyError::UnknownCa,
+            ffi::X509_V_ERR_HOSTNAME_MISMATCH | ffi::X509_V_ERR_IP_ADDRESS_MISMATCH => VerifyError::HostnameMismatch,
+            ffi::X509_V_ERR_CERT_REVOKED => VerifyError::Revoked,
+            code => VerifyError::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            VerifyError::Expired => fmt.write_str("certificate has expired"),
+            VerifyError::NotYetValid => fmt.write_str("certificate is not yet valid"),
+            VerifyError::SelfSigned => fmt.write_str("self-signed certificate"),
+            VerifyError::UnknownCa => fmt.write_str("unknown certificate issuer"),
+            VerifyError::HostnameMismatch => fmt.write_str("certificate doesn't match the host"),
+            VerifyError::Revoked => fmt.write_str("certificate revoked"),
+            VerifyError::Other(code) => write!(fmt, "certificate verification failed with code {}", code),
+        }
+    }
+}
+
 /// An error returned from `ClientBuilder::handshake`.
 #[derive(Debug)]
 pub enum HandshakeError<S> {
@@ -59,6 +266,95 @@ pub enum HandshakeError<S> {
     WouldBlock(ssl::MidHandshakeSslStream<S>),
 }
 
+impl<S> HandshakeError<S> {
+    /// Returns the result of the certificate verification, if the handshake failed
+    /// because of it.
+    ///
+    /// The result tells the reason of the failure apart, e.g. an expired certificate
+    /// from a self-signed one:
+    ///
+    /// ```rust,no_run
+    /// use opentls::sync_io::TlsConnector;
+    /// use std::net::TcpStream;
+    ///
+    /// let connector = TlsConnector::new().unwrap();
+    /// let stream = TcpStream::connect("expired.badssl.com:443").unwrap();
+    /// let err = connector.connect("expired.badssl.com", stream).unwrap_err();
+    ///
+    /// if let Some(result) = err.verify_result() {
+    ///     let expired = result.as_raw() == openssl_sys::X509_V_ERR_CERT_HAS_EXPIRED;
+    ///     println!("{} (expired: {})", result.error_string(), expired);
+    /// }
+    /// ```
+    pub fn verify_result(&self) -> Option<X509VerifyResult> {
+        match *self {
+            HandshakeError::Failure(ref e) => e.verify_result(),
+            HandshakeError::WouldBlock(_) => None,
+        }
+    }
+
+    /// Returns the reason of the failed certificate verification, if the handshake failed
+    /// because of it.
+    ///
+    /// ```rust,no_run
+    /// use opentls::{sync_io::TlsConnector, VerifyError};
+    /// use std::net::TcpStream;
+    ///
+    /// let connector = TlsConnector::new().unwrap();
+    /// let stream = TcpStream::connect("expired.badssl.com:443").unwrap();
+    /// let err = connector.connect("expired.badssl.com", stream).unwrap_err();
+    ///
+    /// match err.verify_error() {
+    ///     Some(VerifyError::Expired) => println!("expired"),
+    ///     Some(e) => println!("invalid certificate: {}", e),
+    ///     None => println!("handshake failed: {:?}", err),
+    /// }
+    /// ```
+    pub fn verify_error(&self) -> Option<VerifyError> {
+        self.verify_result().map(VerifyError::from)
+    }
+
+    /// Returns `true` if the handshake was interrupted by a `WouldBlock` error
+    /// and can be resumed later.
+    pub fn is_would_block(&self) -> bool {
+        match *self {
+            HandshakeError::WouldBlock(_) => true,
+            HandshakeError::Failure(_) => false,
+        }
+    }
+
+    /// Returns the interrupted handshake, if the error was a `WouldBlock`.
+    ///
+    /// The handshake can be resumed with [`MidHandshakeTlsStream::handshake`] once the
+    /// underlying stream is ready to perform I/O again.
+    ///
+    /// [`MidHandshakeTlsStream::handshake`]: crate::sync_io::MidHandshakeTlsStream::handshake
+    pub fn into_mid_handshake(self) -> Option<MidHandshakeTlsStream<S>> {
+        match self {
+            HandshakeError::WouldBlock(s) => Some(MidHandshakeTlsStream::from(s)),
+            HandshakeError::Failure(_) => None,
+        }
+    }
+}
+
+impl<S: fmt::Debug> error::Error for HandshakeError<S> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            HandshakeError::Failure(ref e) => Some(e),
+            HandshakeError::WouldBlock(_) => None,
+        }
+    }
+}
+
+impl<S> fmt::Display for HandshakeError<S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            HandshakeError::Failure(ref e) => write!(fmt, "TLS handshake failed: {}", e),
+            HandshakeError::WouldBlock(_) => fmt.write_str("TLS handshake interrupted by a blocking stream"),
+        }
+    }
+}
+
 impl<S> From<ssl::HandshakeError<S>> for HandshakeError<S> {
     fn from(e: ssl::HandshakeError<S>) -> HandshakeError<S> {
         match e {
@@ -0,0 +1,137 @@
+use std::net::{IpAddr, SocketAddr};
+
+#[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+use std::{convert::TryFrom, io};
+#[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+use url::Url;
+
+/// The host part of a domain (without scheme, port and path).
+///
+/// This is the argument to the `connect` functions. Strings and string slices are
+/// converted into Hosts automatically, as are IP and socket addresses. With one of
+/// the async features, a `Url` can be converted with `Host::try_from`, which fails
+/// if the URL has no host part. `From<Url>` can't be offered next to it, so pass
+/// `Host::try_from(&url)?` to `connect` instead of the URL itself.
+///
+/// Bracketed IPv6 literals such as `[::1]` are stored without the brackets, so
+/// the host can be used as-is for SNI and certificate verification.
+#[derive(Debug)]
+pub struct Host(String);
+
+impl Host {
+    fn new(host: &str) -> Self {
+        let host = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+            Some(ipv6) => ipv6,
+            None => host,
+        };
+
+        Self(host.into())
+    }
+
+    /// The host as a string slice, without consuming self.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The host as string. Consumes self.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn as_string(self) -> String {
+        self.0
+    }
+}
+
+impl From<&str> for Host {
+    fn from(host: &str) -> Self {
+        Self::new(host)
+    }
+}
+
+impl From<String> for Host {
+    fn from(host: String) -> Self {
+        Self::new(&host)
+    }
+}
+
+impl From<&String> for Host {
+    fn from(host: &String) -> Self {
+        Self::new(host)
+    }
+}
+
+impl From<IpAddr> for Host {
+    fn from(addr: IpAddr) -> Self {
+        Self(addr.to_string())
+    }
+}
+
+impl From<SocketAddr> for Host {
+    fn from(addr: SocketAddr) -> Self {
+        Self::from(addr.ip())
+    }
+}
+
+#[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+impl TryFrom<Url> for Host {
+    type Error = crate::Error;
+
+    fn try_from(url: Url) -> crate::Result<Self> {
+        Self::try_from(&url)
+    }
+}
+
+#[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+impl TryFrom<&Url> for Host {
+    type Error = crate::Error;
+
+    fn try_from(url: &Url) -> crate::Result<Self> {
+        match url.host_str() {
+            Some(host) => Ok(Self::new(host)),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "URL has to include a host part.").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+    fn host_from_url() {
+        let url = Url::parse("https://example.com:8443/path").unwrap();
+        assert_eq!(Host::try_from(&url).unwrap().as_string(), "example.com");
+        assert_eq!(Host::try_from(url).unwrap().as_string(), "example.com");
+
+        let url = Url::parse("https://[::1]:8443/").unwrap();
+        assert_eq!(Host::try_from(url).unwrap().as_string(), "::1");
+    }
+
+    #[test]
+    #[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+    fn host_from_url_without_host() {
+        let url = Url::parse("mailto:someone@example.com").unwrap();
+        assert!(Host::try_from(&url).is_err());
+        assert!(Host::try_from(url).is_err());
+    }
+
+    #[test]
+    fn host_from_ipv6() {
+        let addr: SocketAddr = "[::1]:443".parse().unwrap();
+        assert_eq!(Host::from(addr).as_string(), "::1");
+        assert_eq!(Host::from(addr.ip()).as_string(), "::1");
+        assert_eq!(Host::from("[::1]").as_string(), "::1");
+    }
+
+    #[test]
+    fn host_as_str() {
+        let host = Host::from("[::1]");
+        assert_eq!(host.as_str(), "::1");
+        assert_eq!(host.as_string(), "::1");
+    }
+
+    #[test]
+    fn host_from_ipv4() {
+        let addr: SocketAddr = "127.0.0.1:443".parse().unwrap();
+        assert_eq!(Host::from(addr).as_string(), "127.0.0.1");
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt;
 use openssl::{
     pkcs12::Pkcs12,
     pkey::{PKey, Private},
+    stack::Stack,
     x509::X509,
 };
 
@@ -36,14 +37,53 @@ impl Identity {
     /// ```bash
     /// openssl pkcs12 -export -out identity.pfx -inkey key.pem -in cert.pem -certfile chain_certs.pem
     /// ```
+    ///
+    /// A wrong password is told apart from an invalid archive with `Error::is_wrong_password`.
     pub fn from_pkcs12(buf: &[u8], pass: &str) -> crate::Result<Self> {
         let pkcs12 = Pkcs12::from_der(buf)?;
+        #[allow(deprecated)]
         let parsed = pkcs12.parse(pass)?;
 
+        #[allow(deprecated)]
         Ok(Identity {
             pkey: parsed.pkey,
             cert: parsed.cert,
             chain: parsed.chain.into_iter().flatten().collect(),
         })
     }
+
+    /// Creates an identity from a DER-encoded certificate and its DER-encoded private key.
+    ///
+    /// The key may be in PKCS #8 format or in the traditional format of its algorithm, e.g.
+    /// PKCS #1 for RSA keys, but can't be encrypted. The identity has no chain certificates.
+    pub fn from_der(cert_der: &[u8], key_der: &[u8]) -> crate::Result<Self> {
+        Ok(Identity {
+            pkey: PKey::private_key_from_der(key_der)?,
+            cert: X509::from_der(cert_der)?,
+            chain: vec![],
+        })
+    }
+
+    /// Exports the identity as a DER-formatted PKCS #12 archive, encrypted with the specified
+    /// password.
+    ///
+    /// The chain certificates are stored in the same order as they were parsed, so the archive
+    /// can be read back with `from_pkcs12`. The friendly name is shown by tools listing the
+    /// archive's contents.
+    pub fn to_pkcs12(&self, pass: &str, friendly_name: &str) -> crate::Result<Vec<u8>> {
+        let mut builder = Pkcs12::builder();
+        builder.name(friendly_name).pkey(&self.pkey).cert(&self.cert);
+
+        if !self.chain.is_empty() {
+            let mut chain = Stack::new()?;
+
+            for cert in &self.chain {
+                chain.push(cert.clone())?;
+            }
+
+            builder.ca(chain);
+        }
+
+        Ok(builder.build2(pass)?.to_der()?)
+    }
 }
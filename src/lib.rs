@@ -16,6 +16,18 @@
 //!   vendored copy of OpenSSL.
 //! * `io-tokio` - Enables asynchronous IO with Tokio runtime.
 //! * `io-async-std` - Enables asynchronous IO with async-std runtime.
+//! * `io-futures` - Enables asynchronous IO with the `AsyncRead` and `AsyncWrite` traits of
+//!   `futures`, without depending on a particular runtime, e.g. for `smol`.
+//! * `hyper` - Enables `io-tokio` and adds `async_io::HttpsConnector`, the connector of `hyper`
+//!   clients running the TLS handshake over the connections of `hyper-util`'s `HttpConnector`.
+//! * `zeroize` - Overwrites the buffers holding PKCS #12 archives read by the crate with zeros
+//!   once the identity is parsed. Private keys held by OpenSSL are always cleared when freed.
+//! * `serde` - Implements `Serialize` and `Deserialize` for `Certificate`, as base64-encoded DER
+//!   for human-readable formats such as JSON and as raw DER bytes otherwise.
+//! * `native-certs` - Adds `TlsConnectorBuilder::with_native_certs`, trusting the roots of the
+//!   operating system's trust store as loaded by `rustls-native-certs`.
+//! * `insecure-export-secrets` - Adds `TlsStream::master_key`, exposing the master secret of
+//!   the session. Only meant for test harnesses, never enable it in production.
 #![cfg_attr(feature = "docs", feature(doc_cfg))]
 #![warn(missing_docs)]
 #![warn(missing_debug_implementations, rust_2018_idioms)]
@@ -25,28 +37,87 @@
 #[macro_use]
 extern crate log;
 
-#[cfg(all(feature = "io-tokio", feature = "io-async-std"))]
-compile_error!("only one of 'async-std' or 'async-tokio' features must be enabled");
+#[cfg(any(
+    all(feature = "io-tokio", feature = "io-async-std"),
+    all(feature = "io-tokio", feature = "io-futures"),
+    all(feature = "io-async-std", feature = "io-futures"),
+))]
+compile_error!("only one of 'io-tokio', 'io-async-std' or 'io-futures' features must be enabled");
 
-#[cfg(any(feature = "io-tokio", feature = "io-async-std"))]
-#[cfg_attr(feature = "docs", doc(cfg(any(feature = "io-tokio", feature = "io-async-std"))))]
+#[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+#[cfg_attr(
+    feature = "docs",
+    doc(cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures")))
+)]
 pub mod async_io;
+pub mod dtls;
 pub mod sync_io;
 
 mod certificate;
+mod connection_info;
 mod error;
+mod host;
 mod identity;
+mod session;
+#[cfg(all(
+    feature = "zeroize",
+    any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures")
+))]
+mod zeroize;
 
 pub use certificate::Certificate;
-pub use error::{Error, HandshakeError};
+pub use connection_info::ConnectionInfo;
+pub use error::{Error, HandshakeError, VerifyError};
+pub use host::Host;
 pub use identity::Identity;
+pub use openssl::{hash::MessageDigest, ssl::SslVerifyMode, x509::X509VerifyResult};
+pub use session::SessionStore;
 
-use openssl::{error::ErrorStack, ssl::SslContextBuilder};
-use std::result;
+use openssl::ssl::{SslContextBuilder, SslMethod};
+use std::{fmt, io, os::raw::c_int, result, str::FromStr, sync::Once};
 
 /// A typedef of the result-type returned by many methods.
 pub type Result<T> = result::Result<T, Error>;
 
+/// Returns the version string and number of the OpenSSL library linked at runtime, e.g.
+/// `("OpenSSL 3.0.2 15 Mar 2022", 0x3000_0020)`.
+///
+/// The number is laid out as `OPENSSL_VERSION_NUMBER`, `0xMNN00PP0` since OpenSSL 3 and
+/// `0xMNNFFPPS` before, so versions compare numerically. It can differ from the version the crate was compiled against
+/// when OpenSSL is linked dynamically.
+pub fn openssl_version() -> (&'static str, u64) {
+    (openssl::version::version(), openssl::version::number() as u64)
+}
+
+/// Checks that OpenSSL's random number generator is seeded, which every handshake relies on.
+///
+/// OpenSSL seeds it from the operating system, which can fail in sandboxes or minimal
+/// containers without `/dev/urandom` or the `getrandom` system call. Handshakes then fail
+/// with obscure errors, while this returns an error saying so. Entropy from another source
+/// can be added with [`seed_rng`].
+pub fn ensure_rng_seeded() -> Result<()> {
+    openssl_sys::init();
+
+    if unsafe { openssl_sys::RAND_status() } != 1 {
+        let err = io::Error::other("OpenSSL's random number generator is not seeded");
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+/// Mixes `entropy` into the state of OpenSSL's random number generator, as `RAND_seed` does.
+///
+/// The bytes are assumed to be fully random, e.g. read from a hardware generator, as OpenSSL
+/// considers itself seeded once it received enough of them.
+pub fn seed_rng(entropy: &[u8]) {
+    openssl_sys::init();
+
+    for chunk in entropy.chunks(c_int::MAX as usize) {
+        unsafe { openssl_sys::RAND_add(chunk.as_ptr() as *const _, chunk.len() as c_int, chunk.len() as f64) };
+    }
+}
+
 /// SSL/TLS protocol versions.
 #[derive(Debug, Copy, Clone)]
 #[non_exhaustive]
@@ -64,39 +135,164 @@ pub enum Protocol {
     Tlsv11,
     /// The TLS 1.2 protocol.
     Tlsv12,
+    /// The TLS 1.3 protocol.
+    ///
+    /// Requires OpenSSL 1.1.1 or newer, both at build time and at runtime. Building a
+    /// connector or acceptor using it fails otherwise.
+    Tlsv13,
+}
+
+/// Parses a protocol version, e.g. from a configuration file.
+///
+/// The parsing is case-insensitive, and accepts the OpenSSL names such as `TLSv1.2`, as well as
+/// `TLS1.2`, `TLS 1.2` or the bare version `1.2`. TLS 1.0 can be written `1.0` or `1`, and
+/// SSL 3.0 `SSLv3`.
+impl FromStr for Protocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.trim().to_ascii_lowercase();
+
+        if let Some(version) = lower.strip_prefix("ssl") {
+            return match version.trim_start_matches(['v', ' ']) {
+                "3" | "3.0" => Ok(Protocol::Sslv3),
+                _ => Err(unknown_protocol(s)),
+            };
+        }
+
+        let version = lower.strip_prefix("tls").unwrap_or(&lower);
+
+        match version.trim_start_matches(['v', ' ']) {
+            "1" | "1.0" => Ok(Protocol::Tlsv10),
+            "1.1" => Ok(Protocol::Tlsv11),
+            "1.2" => Ok(Protocol::Tlsv12),
+            "1.3" => Ok(Protocol::Tlsv13),
+            _ => Err(unknown_protocol(s)),
+        }
+    }
+}
+
+fn unknown_protocol(s: &str) -> Error {
+    let msg = format!("unknown protocol version `{}`", s);
+    io::Error::new(io::ErrorKind::InvalidInput, msg).into()
+}
+
+/// Displays the OpenSSL name of the protocol, such as `TLSv1.2`, which `FromStr` parses back.
+impl fmt::Display for Protocol {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(match self {
+            Protocol::Sslv3 => "SSLv3",
+            Protocol::Tlsv10 => "TLSv1",
+            Protocol::Tlsv11 => "TLSv1.1",
+            Protocol::Tlsv12 => "TLSv1.2",
+            Protocol::Tlsv13 => "TLSv1.3",
+        })
+    }
+}
+
+/// Maximum TLS record sizes negotiable with the max_fragment_length extension of
+/// [RFC 6066].
+///
+/// [RFC 6066]: https://tools.ietf.org/html/rfc6066#section-4
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MaxFragmentLength {
+    /// Records of at most 512 bytes.
+    Bytes512,
+    /// Records of at most 1024 bytes.
+    Bytes1024,
+    /// Records of at most 2048 bytes.
+    Bytes2048,
+    /// Records of at most 4096 bytes.
+    Bytes4096,
+}
+
+/// The [Mozilla server configuration] an acceptor starts from.
+///
+/// [Mozilla server configuration]: https://wiki.mozilla.org/Security/Server_Side_TLS
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SecurityProfile {
+    /// The intermediate configuration, supporting TLS 1.2 and older clients with the protocol
+    /// versions of the acceptor.
+    Intermediate,
+    /// The modern configuration (version 5), only supporting TLS 1.3 clients.
+    ///
+    /// Requires OpenSSL 1.1.1 or newer.
+    Modern,
+}
+
+/// The cipher list once a PSK callback is set: the TLS 1.2 suites authenticated with a PSK
+/// first, then the OpenSSL defaults.
+const PSK_CIPHER_LIST: &str = "aPSK:DEFAULT:!aNULL:!eNULL:!MD5:!3DES:!DES:!RC4:!IDEA:!SEED:!aDSS:!SRP";
+
+/// The OpenSSL method of a builder, as `SslMethod` doesn't implement `Debug`.
+#[derive(Copy, Clone)]
+struct Method(SslMethod);
+
+impl fmt::Debug for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SslMethod").finish()
+    }
+}
+
+/// Points OpenSSL to the system certificates, if it wasn't built to find them.
+fn init_openssl_env() {
+    static ONCE: Once = Once::new();
+    // The variables are only ever set once, before any connector is built.
+    ONCE.call_once(|| unsafe { openssl_probe::init_openssl_env_vars() });
+}
+
+/// Returns `true` if the OpenSSL linked at runtime supports TLS 1.3, which may differ from
+/// the one the crate was built against.
+fn tls13_supported() -> bool {
+    cfg!(have_ossl111) && openssl::version::number() >= 0x1010_1000
+}
+
+fn tls13_unsupported() -> Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TLS 1.3 is not supported by the OpenSSL in use",
+    )
+    .into()
 }
 
 #[cfg(have_min_max_version)]
-fn supported_protocols(
-    min: Option<Protocol>,
-    max: Option<Protocol>,
-    ctx: &mut SslContextBuilder,
-) -> std::result::Result<(), ErrorStack> {
+fn supported_protocols(min: Option<Protocol>, max: Option<Protocol>, ctx: &mut SslContextBuilder) -> Result<()> {
     use openssl::ssl::SslVersion;
 
-    fn cvt(p: Protocol) -> SslVersion {
+    fn cvt(p: Protocol) -> Result<SslVersion> {
         match p {
-            Protocol::Sslv3 => SslVersion::SSL3,
-            Protocol::Tlsv10 => SslVersion::TLS1,
-            Protocol::Tlsv11 => SslVersion::TLS1_1,
-            Protocol::Tlsv12 => SslVersion::TLS1_2,
+            Protocol::Sslv3 => Ok(SslVersion::SSL3),
+            Protocol::Tlsv10 => Ok(SslVersion::TLS1),
+            Protocol::Tlsv11 => Ok(SslVersion::TLS1_1),
+            Protocol::Tlsv12 => Ok(SslVersion::TLS1_2),
+            #[cfg(have_ossl111)]
+            Protocol::Tlsv13 if tls13_supported() => Ok(SslVersion::TLS1_3),
+            Protocol::Tlsv13 => Err(tls13_unsupported()),
         }
     }
 
-    ctx.set_min_proto_version(min.map(cvt))?;
-    ctx.set_max_proto_version(max.map(cvt))?;
+    ctx.set_min_proto_version(min.map(cvt).transpose()?)?;
+    ctx.set_max_proto_version(max.map(cvt).transpose()?)?;
+
+    // The acceptor disables TLS 1.3 by default, which asking for it explicitly overrides.
+    #[cfg(have_ossl111)]
+    if matches!(min, Some(Protocol::Tlsv13)) || matches!(max, Some(Protocol::Tlsv13)) {
+        ctx.clear_options(openssl::ssl::SslOptions::NO_TLSV1_3);
+    }
 
     Ok(())
 }
 
 #[cfg(not(have_min_max_version))]
-fn supported_protocols(
-    min: Option<Protocol>,
-    max: Option<Protocol>,
-    ctx: &mut SslContextBuilder,
-) -> std::result::Result<(), ErrorStack> {
+fn supported_protocols(min: Option<Protocol>, max: Option<Protocol>, ctx: &mut SslContextBuilder) -> Result<()> {
     use openssl::ssl::SslOptions;
 
+    // TLS 1.3 came with OpenSSL 1.1.1, long after the version bounds of 1.1.0.
+    if matches!(min, Some(Protocol::Tlsv13)) || matches!(max, Some(Protocol::Tlsv13)) {
+        return Err(tls13_unsupported());
+    }
+
     let no_ssl_mask = SslOptions::NO_SSLV2
         | SslOptions::NO_SSLV3
         | SslOptions::NO_TLSV1
@@ -110,12 +306,12 @@ fn supported_protocols(
         Some(Protocol::Sslv3) => SslOptions::NO_SSLV2,
         Some(Protocol::Tlsv10) => SslOptions::NO_SSLV2 | SslOptions::NO_SSLV3,
         Some(Protocol::Tlsv11) => SslOptions::NO_SSLV2 | SslOptions::NO_SSLV3 | SslOptions::NO_TLSV1,
-        Some(Protocol::Tlsv12) => {
+        Some(Protocol::Tlsv12) | Some(Protocol::Tlsv13) => {
             SslOptions::NO_SSLV2 | SslOptions::NO_SSLV3 | SslOptions::NO_TLSV1 | SslOptions::NO_TLSV1_1
         }
     };
     options |= match max {
-        None | Some(Protocol::Tlsv12) => SslOptions::empty(),
+        None | Some(Protocol::Tlsv12) | Some(Protocol::Tlsv13) => SslOptions::empty(),
         Some(Protocol::Tlsv11) => SslOptions::NO_TLSV1_2,
         Some(Protocol::Tlsv10) => SslOptions::NO_TLSV1_1 | SslOptions::NO_TLSV1_2,
         Some(Protocol::Sslv3) => SslOptions::NO_TLSV1 | SslOptions::NO_TLSV1_1 | SslOptions::NO_TLSV1_2,
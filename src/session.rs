@@ -0,0 +1,238 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use openssl::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{Ssl, SslConnectorBuilder, SslRef, SslSession, SslSessionCacheMode},
+};
+
+/// The number of hosts the in-memory session store remembers before evicting the
+/// least recently used one.
+const SESSION_CACHE_CAPACITY: usize = 256;
+
+/// A storage for TLS sessions, used by the connector to resume sessions on later
+/// connections to the same host.
+///
+/// Sessions are stored DER-encoded, so they can be shared between processes, e.g. in
+/// Redis or shared memory. Implementations should keep only the latest session per key.
+///
+/// Keys are host names only, so a store must only be shared by connectors with the same
+/// verification settings, trust roots and client identity. A resumed session skips the
+/// certificate verification, a connector resuming a session stored by a connector trusting
+/// other roots accepts a server it would have rejected. Connectors not verifying the server
+/// fully, e.g. with `danger_accept_invalid_certs` or a verify callback, never store or resume
+/// sessions.
+///
+/// # Warning
+///
+/// A stored session contains the secret needed to resume it. Anyone with access to the
+/// store can impersonate the client to the server the session was negotiated with.
+pub trait SessionStore: Send + Sync {
+    /// Returns the DER-encoded session stored for `key`, if any.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Stores the DER-encoded session for `key`, replacing any previous one.
+    fn put(&self, key: &str, session_der: Vec<u8>);
+
+    /// Removes the session stored for `key`, called when OpenSSL invalidates it.
+    ///
+    /// Does nothing by default.
+    fn remove(&self, key: &str) {
+        let _ = key;
+    }
+}
+
+/// The key the session of a connection is stored with, kept in the `Ssl` of the
+/// connection so the session callback can find it.
+#[derive(Debug)]
+struct SessionKey(String);
+
+fn session_key_index() -> Result<Index<Ssl, SessionKey>, ErrorStack> {
+    static INDEX: OnceLock<Index<Ssl, SessionKey>> = OnceLock::new();
+
+    if let Some(index) = INDEX.get() {
+        return Ok(*index);
+    }
+
+    let index = Ssl::new_ex_index()?;
+    Ok(*INDEX.get_or_init(|| index))
+}
+
+/// The session cache setting of a connector builder.
+///
+/// A clone of a builder gets an in-memory cache of its own, its verification settings may
+/// differ from the original ones. A store set by the user is shared.
+#[derive(Debug)]
+pub(crate) enum SessionCacheConfig {
+    Memory(SessionCache),
+    Store(SessionCache),
+}
+
+impl SessionCacheConfig {
+    pub(crate) fn cache(&self) -> &SessionCache {
+        match self {
+            SessionCacheConfig::Memory(cache) | SessionCacheConfig::Store(cache) => cache,
+        }
+    }
+}
+
+impl Clone for SessionCacheConfig {
+    fn clone(&self) -> Self {
+        match self {
+            SessionCacheConfig::Memory(_) => SessionCacheConfig::Memory(SessionCache::memory()),
+            SessionCacheConfig::Store(cache) => SessionCacheConfig::Store(cache.clone()),
+        }
+    }
+}
+
+/// Client-side session caching on top of a `SessionStore`.
+#[derive(Clone)]
+pub(crate) struct SessionCache {
+    store: Arc<dyn SessionStore>,
+    // The ID of the latest session stored or resumed for each key, so the session can be
+    // removed from the store when OpenSSL invalidates it.
+    ids: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl fmt::Debug for SessionCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionCache").finish()
+    }
+}
+
+impl SessionCache {
+    /// A cache keeping the sessions in memory.
+    pub(crate) fn memory() -> Self {
+        Self::store(Arc::new(MemorySessionStore::default()))
+    }
+
+    /// A cache keeping the sessions in the given store.
+    pub(crate) fn store(store: Arc<dyn SessionStore>) -> Self {
+        Self {
+            store,
+            ids: Arc::default(),
+        }
+    }
+
+    /// Installs the session callbacks storing new sessions of the connector and removing the
+    /// invalidated ones.
+    pub(crate) fn install(&self, connector: &mut SslConnectorBuilder) -> Result<(), ErrorStack> {
+        let index = session_key_index()?;
+        let (store, ids) = (self.store.clone(), self.ids.clone());
+
+        // Without the internal store, OpenSSL only calls the remove callback for sessions it
+        // invalidates, not when the context is freed.
+        connector.set_session_cache_mode(SslSessionCacheMode::CLIENT | SslSessionCacheMode::NO_INTERNAL_STORE);
+        connector.set_new_session_callback(move |ssl, session| {
+            let key = match ssl.ex_data(index) {
+                Some(SessionKey(key)) => key,
+                None => return,
+            };
+
+            match session.to_der() {
+                Ok(der) => {
+                    ids.lock().unwrap().insert(key.clone(), session.id().to_vec());
+                    store.put(key, der);
+                }
+                Err(err) => debug!("session to_der error: {:?}", err),
+            }
+        });
+
+        let (store, ids) = (self.store.clone(), self.ids.clone());
+
+        connector.set_remove_session_callback(move |_, session| {
+            let mut ids = ids.lock().unwrap();
+
+            // A newer session of the key may have replaced the removed one in the store.
+            let key = match ids.iter().find(|(_, id)| id.as_slice() == session.id()) {
+                Some((key, _)) => key.clone(),
+                None => return,
+            };
+
+            ids.remove(&key);
+            store.remove(&key);
+        });
+
+        Ok(())
+    }
+
+    /// Prepares a connection to `key`, resuming a stored session if there is one.
+    pub(crate) fn prepare(&self, ssl: &mut SslRef, key: &str) -> Result<(), ErrorStack> {
+        ssl.set_ex_data(session_key_index()?, SessionKey(key.into()));
+
+        let session = match self.store.get(key).map(|der| SslSession::from_der(&der)) {
+            Some(Ok(session)) => session,
+            Some(Err(err)) => {
+                debug!("session from_der error: {:?}", err);
+                return Ok(());
+            }
+            None => return Ok(()),
+        };
+
+        self.ids.lock().unwrap().insert(key.into(), session.id().to_vec());
+
+        // The session is only resumed if the server accepts it, OpenSSL falls back to a
+        // full handshake otherwise.
+        unsafe { ssl.set_session(&session) }
+    }
+}
+
+/// An in-memory session store, keeping the latest session of each host.
+#[derive(Debug, Default)]
+struct MemorySessionStore(Mutex<MemorySessions>);
+
+#[derive(Debug, Default)]
+struct MemorySessions {
+    sessions: HashMap<String, Vec<u8>>,
+    // Least recently used host first.
+    order: VecDeque<String>,
+}
+
+impl MemorySessions {
+    fn touch(&mut self, key: &str) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            if let Some(key) = self.order.remove(pos) {
+                self.order.push_back(key);
+            }
+        }
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut inner = self.0.lock().unwrap();
+        let session = inner.sessions.get(key)?.clone();
+        inner.touch(key);
+
+        Some(session)
+    }
+
+    fn put(&self, key: &str, session_der: Vec<u8>) {
+        let mut inner = self.0.lock().unwrap();
+
+        if inner.sessions.insert(key.into(), session_der).is_some() {
+            inner.touch(key);
+            return;
+        }
+
+        inner.order.push_back(key.into());
+
+        if inner.order.len() > SESSION_CACHE_CAPACITY {
+            if let Some(evicted) = inner.order.pop_front() {
+                inner.sessions.remove(&evicted);
+            }
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let mut inner = self.0.lock().unwrap();
+
+        if inner.sessions.remove(key).is_some() {
+            inner.order.retain(|k| k != key);
+        }
+    }
+}
@@ -1,11 +1,14 @@
 mod builder;
 
-use std::{fmt, io};
+use std::{fmt, fs, io, path::Path};
 
 pub use builder::TlsAcceptorBuilder;
-use openssl::ssl::SslAcceptor;
+use openssl::ssl::{SslAcceptor, SslMethod};
 
-use crate::{sync_io::TlsStream, HandshakeError, Identity, Protocol};
+#[cfg(have_ossl111)]
+use openssl::ssl::{Ssl, SslStream};
+
+use crate::{sync_io::TlsStream, HandshakeError, Identity, Method, Protocol, SecurityProfile};
 
 /// A builder for server-side TLS connections.
 ///
@@ -62,14 +65,43 @@ impl TlsAcceptor {
         Self::builder(identity).build()
     }
 
+    /// Creates an acceptor with default settings from a PKCS #12 file.
+    ///
+    /// The archive is decrypted with `password`, see `Identity::from_pkcs12`.
+    pub fn from_pkcs12_file(path: impl AsRef<Path>, password: &str) -> crate::Result<TlsAcceptor> {
+        let buf = fs::read(path)?;
+        Self::new(Identity::from_pkcs12(&buf, password)?)
+    }
+
     /// Returns a new builder for a `TlsAcceptor`.
     ///
     /// The identity acts as the server's private key/certificate chain.
     pub fn builder(identity: Identity) -> TlsAcceptorBuilder {
         TlsAcceptorBuilder {
+            method: Method(SslMethod::tls()),
+            security_profile: SecurityProfile::Intermediate,
             identity,
-            min_protocol: Some(Protocol::Tlsv10),
+            identities: vec![],
+            min_protocol: Some(Protocol::Tlsv12),
             max_protocol: None,
+            #[cfg(have_ossl110)]
+            security_level: None,
+            ocsp_response: None,
+            dh_params: None,
+            ticket_key: None,
+            disable_tickets: false,
+            session_cache_size: None,
+            session_timeout: None,
+            read_ahead: false,
+            psk_server_callback: None,
+            root_certificates: vec![],
+            client_ca_names: vec![],
+            request_client_auth: false,
+            #[cfg(have_ossl111)]
+            max_early_data: 0,
+            #[cfg(have_ossl111)]
+            groups_list: None,
+            openssl_callbacks: vec![],
         }
     }
 
@@ -79,11 +111,61 @@ impl TlsAcceptor {
     /// the handshake, a `HandshakeError::WouldBlock` error will be returned
     /// which can be used to restart the handshake when the socket is ready
     /// again.
+    ///
+    /// # Examples
+    ///
+    /// Resuming the handshake of a nonblocking server, e.g. one driven by `epoll`, which
+    /// parks partial handshakes until their socket is ready:
+    ///
+    /// ```rust,no_run
+    /// use opentls::{sync_io::TlsAcceptor, Identity};
+    /// use std::net::TcpListener;
+    ///
+    /// let identity = Identity::from_pkcs12(include_bytes!("../../tests/identity.p12"), "mypass").unwrap();
+    /// let acceptor = TlsAcceptor::new(identity).unwrap();
+    /// let listener = TcpListener::bind("0.0.0.0:8443").unwrap();
+    ///
+    /// let (stream, _) = listener.accept().unwrap();
+    /// stream.set_nonblocking(true).unwrap();
+    ///
+    /// let mut result = acceptor.accept(stream);
+    ///
+    /// let stream = loop {
+    ///     match result {
+    ///         Ok(stream) => break stream,
+    ///         Err(e) => match e.into_mid_handshake() {
+    ///             Some(mid) => {
+    ///                 // park `mid` until the socket is readable or writable,
+    ///                 // e.g. with `epoll`
+    ///                 result = mid.handshake();
+    ///             }
+    ///             None => panic!("handshake failed"),
+    ///         },
+    ///     }
+    /// };
+    /// ```
     pub fn accept<S>(&self, stream: S) -> Result<TlsStream<S>, HandshakeError<S>>
     where
         S: io::Read + io::Write,
     {
         let s = self.0.accept(stream)?;
-        Ok(TlsStream(s))
+        Ok(TlsStream::new(s))
+    }
+
+    /// Starts a connection without completing the handshake, so TLS 1.3 early data sent by
+    /// the client can be read with [`TlsStream::read_early_data`].
+    ///
+    /// Early data is only received if enabled with `TlsAcceptorBuilder::set_max_early_data`.
+    /// Once all early data was read, complete the handshake with [`TlsStream::handshake`].
+    /// Requires OpenSSL 1.1.1 or newer.
+    #[cfg(have_ossl111)]
+    pub fn accept_early<S>(&self, stream: S) -> crate::Result<TlsStream<S>>
+    where
+        S: io::Read + io::Write,
+    {
+        let mut ssl = Ssl::new(self.0.context())?;
+        ssl.set_accept_state();
+
+        Ok(TlsStream::new(SslStream::new(ssl, stream)?))
     }
 }
@@ -1,20 +1,121 @@
 use super::{Identity, Protocol, TlsAcceptor};
-use openssl::ssl::{SslAcceptor, SslMethod};
+use crate::{Certificate, Method, SecurityProfile};
+use foreign_types::ForeignTypeRef;
+use openssl::{
+    dh::Dh,
+    error::ErrorStack,
+    ssl::{SslAcceptor, SslAcceptorBuilder, SslMethod, SslOptions, SslVerifyMode},
+    x509::X509Ref,
+};
+use openssl_sys as ffi;
+use std::{
+    convert::TryFrom,
+    fmt, io, iter,
+    os::raw::{c_long, c_void},
+    sync::Arc,
+};
+
+// `SSL_CTX_set_tlsext_ticket_keys` is a macro over `SSL_CTX_ctrl`.
+const SSL_CTRL_SET_TLSEXT_TICKET_KEYS: i32 = 59;
+
+extern "C" {
+    // Not exposed by `openssl-sys`.
+    fn SSL_CTX_set_timeout(ctx: *mut ffi::SSL_CTX, timeout: c_long) -> c_long;
+}
 
 /// A builder for `TlsAcceptor`s.
-#[derive(Debug)]
+///
+/// The builder can be cloned to derive several configurations from a common base.
+#[derive(Clone, Debug)]
 pub struct TlsAcceptorBuilder {
+    pub(crate) method: Method,
+    pub(crate) security_profile: SecurityProfile,
     pub(crate) identity: Identity,
+    pub(crate) identities: Vec<Identity>,
     pub(crate) min_protocol: Option<Protocol>,
     pub(crate) max_protocol: Option<Protocol>,
+    #[cfg(have_ossl110)]
+    pub(crate) security_level: Option<u32>,
+    pub(crate) ocsp_response: Option<Vec<u8>>,
+    pub(crate) dh_params: Option<Vec<u8>>,
+    pub(crate) ticket_key: Option<Vec<u8>>,
+    pub(crate) disable_tickets: bool,
+    pub(crate) session_cache_size: Option<usize>,
+    pub(crate) session_timeout: Option<u32>,
+    pub(crate) read_ahead: bool,
+    pub(crate) psk_server_callback: Option<PskServerCallback>,
+    pub(crate) root_certificates: Vec<Certificate>,
+    pub(crate) client_ca_names: Vec<Certificate>,
+    pub(crate) request_client_auth: bool,
+    #[cfg(have_ossl111)]
+    pub(crate) max_early_data: u32,
+    #[cfg(have_ossl111)]
+    pub(crate) groups_list: Option<String>,
+    pub(crate) openssl_callbacks: Vec<OpensslCallback>,
+}
+
+/// A user-provided callback returning the PSK key for a client identity.
+#[derive(Clone)]
+pub(crate) struct PskServerCallback(Arc<PskServerFn>);
+
+type PskServerFn = dyn Fn(Option<&[u8]>) -> Option<Vec<u8>> + Send + Sync;
+
+impl fmt::Debug for PskServerCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PskServerCallback").finish()
+    }
+}
+
+/// A user-provided callback configuring the OpenSSL acceptor directly.
+#[derive(Clone)]
+pub(crate) struct OpensslCallback(Arc<OpensslFn>);
+
+type OpensslFn = dyn Fn(&mut SslAcceptorBuilder) -> Result<(), ErrorStack> + Send + Sync;
+
+impl fmt::Debug for OpensslCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpensslCallback").finish()
+    }
 }
 
 impl TlsAcceptorBuilder {
+    /// Sets the OpenSSL method the acceptor is created with, e.g. `SslMethod::tls_server()`.
+    ///
+    /// Defaults to `SslMethod::tls()`.
+    pub fn with_method(&mut self, method: SslMethod) -> &mut Self {
+        self.method = Method(method);
+        self
+    }
+
+    /// Adds another identity with a different type of key, e.g. an ECDSA certificate next to
+    /// an RSA one.
+    ///
+    /// OpenSSL holds one certificate per type of key, and picks the one to present from the
+    /// signature algorithms the client supports. An identity whose key has the same type as an
+    /// earlier one replaces it.
+    pub fn add_identity(&mut self, identity: Identity) -> &mut Self {
+        self.identities.push(identity);
+        self
+    }
+
+    /// Selects the Mozilla configuration the acceptor starts from, with its protocol versions
+    /// and cipher suites.
+    ///
+    /// The modern profile only supports TLS 1.3, so the minimum protocol version is ignored
+    /// with it. Building the acceptor fails if OpenSSL doesn't support TLS 1.3.
+    ///
+    /// Defaults to `SecurityProfile::Intermediate`.
+    pub fn security_profile(&mut self, profile: SecurityProfile) -> &mut Self {
+        self.security_profile = profile;
+        self
+    }
+
     /// Sets the minimum supported protocol version.
     ///
     /// A value of `None` enables support for the oldest protocols supported by the implementation.
     ///
-    /// Defaults to `Some(Protocol::Tlsv10)`.
+    /// Defaults to `Some(Protocol::Tlsv12)`. TLS 1.0 and 1.1 are deprecated and have to be
+    /// enabled explicitly, e.g. for legacy peers.
     pub fn min_protocol_version(&mut self, protocol: Option<Protocol>) -> &mut Self {
         self.min_protocol = protocol;
         self
@@ -30,18 +131,355 @@ impl TlsAcceptorBuilder {
         self
     }
 
+    /// Sets the OpenSSL security level, from 0 to 5.
+    ///
+    /// Higher levels enforce larger minimum key sizes and disable weak algorithms. The
+    /// identity's own key has to satisfy the level, or building the acceptor fails.
+    ///
+    /// Defaults to the level OpenSSL was configured with. Requires OpenSSL 1.1.0 or newer.
+    #[cfg(have_ossl110)]
+    pub fn set_security_level(&mut self, level: u32) -> &mut Self {
+        self.security_level = Some(level);
+        self
+    }
+
+    /// Sets a DER-encoded OCSP response to staple to the handshake of clients requesting one.
+    ///
+    /// The response has to be fetched from the CA's responder out of band. OCSP responses
+    /// expire, so a long-running server should periodically fetch a fresh one and build a new
+    /// acceptor with it.
+    pub fn set_ocsp_response(&mut self, der: Vec<u8>) -> &mut Self {
+        self.ocsp_response = Some(der);
+        self
+    }
+
+    /// Sets the Diffie-Hellman parameters of the DHE cipher suites, from PEM-encoded
+    /// `DH PARAMETERS`.
+    ///
+    /// The parameters should be at least 2048 bits long. They can be generated with
+    /// `openssl dhparam -out dhparams.pem 2048`, or taken from a standard group with
+    /// `openssl genpkey -genparam -algorithm DH -pkeyopt dh_param:ffdhe2048`. Fails if the PEM
+    /// can't be parsed.
+    ///
+    /// Defaults to the 2048-bit `ffdhe2048` group of the Mozilla intermediate configuration.
+    pub fn set_dh_params(&mut self, pem: &[u8]) -> crate::Result<&mut Self> {
+        let dh = Dh::params_from_pem(pem)?;
+        self.dh_params = Some(dh.params_to_der()?);
+        Ok(self)
+    }
+
+    /// Sets the key used to encrypt and decrypt session tickets.
+    ///
+    /// By default every acceptor generates a random key, so a session can only be resumed by the
+    /// acceptor that issued its ticket. Servers of a cluster sharing the same key can resume the
+    /// sessions of each other.
+    ///
+    /// With OpenSSL 1.1.0 and newer the key is 80 bytes long: a 16-byte key name identifying the
+    /// key in issued tickets, a 32-byte HMAC secret and a 32-byte AES key. OpenSSL 1.0.2 expects
+    /// 48 bytes, with a 16-byte HMAC secret and AES key. Building the acceptor fails if the length
+    /// doesn't match.
+    ///
+    /// To rotate the key, build a new acceptor with a new key. Tickets issued with the old key
+    /// can no longer be resumed.
+    pub fn set_ticket_key(&mut self, key: &[u8]) -> &mut Self {
+        self.ticket_key = Some(key.to_vec());
+        self
+    }
+
+    /// Controls issuing session tickets.
+    ///
+    /// Without tickets, sessions can only be resumed from the acceptor's own session cache.
+    ///
+    /// Defaults to `false`.
+    pub fn disable_tickets(&mut self, disable: bool) -> &mut Self {
+        self.disable_tickets = disable;
+        self
+    }
+
+    /// Sets the maximum number of sessions kept in the acceptor's session cache, evicting the
+    /// oldest ones once it is full. A value of `0` leaves the cache unbounded.
+    ///
+    /// Defaults to 20480 sessions.
+    pub fn set_session_cache_size(&mut self, size: usize) -> &mut Self {
+        self.session_cache_size = Some(size);
+        self
+    }
+
+    /// Sets the number of seconds after which a session, cached or in a ticket, can no longer
+    /// be resumed.
+    ///
+    /// Defaults to 7200 seconds.
+    pub fn set_session_timeout(&mut self, secs: u32) -> &mut Self {
+        self.session_timeout = Some(secs);
+        self
+    }
+
+    /// Controls OpenSSL's read-ahead, reading as many bytes from the stream as are
+    /// available instead of one record at a time.
+    ///
+    /// This saves system calls on bulk transfers, at the cost of buffering up to a full
+    /// record more inside OpenSSL. The BIO buffer size itself can't be tuned, since the
+    /// stream is read through a custom BIO.
+    ///
+    /// Defaults to `false`.
+    pub fn set_read_ahead(&mut self, read_ahead: bool) -> &mut Self {
+        self.read_ahead = read_ahead;
+        self
+    }
+
+    /// Sets a callback looking up the pre-shared key (PSK) of a client authenticating with
+    /// one instead of a certificate.
+    ///
+    /// The callback receives the PSK identity sent by the client and returns its key, or `None`
+    /// to abort the handshake. Clients not using a PSK are served with the certificate of the
+    /// identity as usual.
+    ///
+    /// The PSK cipher suites are preferred for TLS 1.2, followed by the OpenSSL default suites
+    /// instead of the Mozilla intermediate ones.
+    pub fn set_psk_server_callback<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(Option<&[u8]>) -> Option<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.psk_server_callback = Some(PskServerCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Adds a certificate to the set of roots client certificates are verified against.
+    ///
+    /// The system roots are not trusted for client certificates. Defaults to an empty set.
+    pub fn add_root_certificate(&mut self, cert: Certificate) -> &mut Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    /// Adds the subject of a CA certificate to the list of names advertised to clients when
+    /// requesting a certificate.
+    ///
+    /// Clients with several certificates pick the one to send by this list. The certificate is
+    /// only advertised, add it with `add_root_certificate` as well to trust the certificates
+    /// it issued. Defaults to an empty list.
+    pub fn add_client_ca_name(&mut self, cert: Certificate) -> &mut Self {
+        self.client_ca_names.push(cert);
+        self
+    }
+
+    /// Requests a certificate from clients, without requiring one.
+    ///
+    /// A certificate presented by the client has to be issued by one of the roots added with
+    /// `add_root_certificate`, or the handshake fails. Clients not presenting a certificate are
+    /// accepted, so check `TlsStream::peer_certificate` to tell them apart.
+    pub fn request_client_auth(&mut self) -> &mut Self {
+        self.request_client_auth = true;
+        self
+    }
+
+    /// Sets the maximum number of bytes of TLS 1.3 early data ("0-RTT") accepted from a
+    /// client resuming a session, read with `TlsStream::read_early_data` on connections
+    /// started with `TlsAcceptor::accept_early`.
+    ///
+    /// Early data is only supported by TLS 1.3, which the acceptor doesn't negotiate by
+    /// default; a value above zero enables it. Resumption relies on session tickets, so
+    /// `disable_tickets` disables early data as well. To detect replays, OpenSSL keeps the
+    /// sessions in the acceptor's cache, and a session can't be resumed once its connection
+    /// was dropped without `TlsStream::shutdown`.
+    ///
+    /// Defaults to `0`, rejecting early data. Requires OpenSSL 1.1.1 or newer.
+    ///
+    /// # Warning
+    ///
+    /// Early data can be replayed by an attacker. OpenSSL rejects a ticket used twice with
+    /// the same acceptor, but not across acceptors or servers sharing a ticket key, so the
+    /// application is responsible for only acting on early data which is safe to process
+    /// more than once.
+    #[cfg(have_ossl111)]
+    pub fn set_max_early_data(&mut self, bytes: u32) -> &mut Self {
+        self.max_early_data = bytes;
+        self
+    }
+
+    /// Sets the supported key exchange groups in order of preference, e.g. `"X25519:P-256"`.
+    ///
+    /// Building the acceptor fails if the list contains a group unknown to OpenSSL. Defaults to
+    /// the OpenSSL defaults. Requires OpenSSL 1.1.1 or newer.
+    #[cfg(have_ossl111)]
+    pub fn set_groups_list(&mut self, groups: &str) -> &mut Self {
+        self.groups_list = Some(groups.into());
+        self
+    }
+
+    /// Adds a callback configuring the underlying OpenSSL acceptor, for settings this builder
+    /// doesn't wrap.
+    ///
+    /// The acceptor starts from the Mozilla intermediate configuration. The callbacks run in the
+    /// order they were added, after every other setting of the builder, each time the acceptor
+    /// is built. An error returned by a callback fails the build.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use opentls::{sync_io::TlsAcceptor, Identity};
+    ///
+    /// # let der = std::fs::read("identity.p12").unwrap();
+    /// # let identity = Identity::from_pkcs12(&der, "mypass").unwrap();
+    /// let acceptor = TlsAcceptor::builder(identity)
+    ///     .with_openssl(|acceptor| acceptor.set_cipher_list("ECDHE+AESGCM"))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn with_openssl<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&mut SslAcceptorBuilder) -> Result<(), ErrorStack> + Send + Sync + 'static,
+    {
+        self.openssl_callbacks.push(OpensslCallback(Arc::new(callback)));
+        self
+    }
+
     /// Creates a new `TlsAcceptor`.
     pub fn build(&self) -> crate::Result<TlsAcceptor> {
-        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
-        acceptor.set_private_key(&self.identity.pkey)?;
-        acceptor.set_certificate(&self.identity.cert)?;
+        let (mut acceptor, min_protocol) = match self.security_profile {
+            SecurityProfile::Intermediate => (SslAcceptor::mozilla_intermediate(self.method.0)?, self.min_protocol),
+            #[cfg(have_ossl111)]
+            SecurityProfile::Modern => (SslAcceptor::mozilla_modern_v5(self.method.0)?, Some(Protocol::Tlsv13)),
+            #[cfg(not(have_ossl111))]
+            SecurityProfile::Modern => return Err(crate::tls13_unsupported()),
+        };
 
-        for cert in self.identity.chain.iter().rev() {
-            acceptor.add_extra_chain_cert(cert.to_owned())?;
+        #[cfg(have_ossl110)]
+        if let Some(level) = self.security_level {
+            acceptor.set_security_level(level);
         }
 
-        crate::supported_protocols(self.min_protocol, self.max_protocol, &mut acceptor)?;
+        // Each certificate gets its own chain, as the extra chain is sent with any of them.
+        for identity in iter::once(&self.identity).chain(&self.identities) {
+            acceptor.set_private_key(&identity.pkey)?;
+            acceptor.set_certificate(&identity.cert)?;
+
+            for cert in identity.chain.iter().rev() {
+                add_chain_cert(&mut acceptor, cert)?;
+            }
+        }
+
+        crate::supported_protocols(min_protocol, self.max_protocol, &mut acceptor)?;
+
+        if let Some(ref response) = self.ocsp_response {
+            let response = response.clone();
+
+            acceptor.set_status_callback(move |ssl| {
+                ssl.set_ocsp_status(&response)?;
+                Ok(true)
+            })?;
+        }
+
+        if let Some(ref der) = self.dh_params {
+            let dh = Dh::params_from_der(der)?;
+            acceptor.set_tmp_dh(&dh)?;
+        }
+
+        if self.disable_tickets {
+            acceptor.set_options(SslOptions::NO_TICKET);
+        }
+
+        if let Some(size) = self.session_cache_size {
+            acceptor.set_session_cache_size(i32::try_from(size).unwrap_or(i32::MAX));
+        }
+
+        if let Some(secs) = self.session_timeout {
+            unsafe { SSL_CTX_set_timeout(acceptor.as_ptr(), secs.into()) };
+        }
+
+        if self.read_ahead {
+            acceptor.set_read_ahead(true);
+        }
+
+        #[cfg(have_ossl111)]
+        if self.max_early_data > 0 {
+            acceptor.clear_options(SslOptions::NO_TLSV1_3);
+            acceptor.set_max_early_data(self.max_early_data)?;
+        }
+
+        #[cfg(have_ossl111)]
+        if let Some(ref groups) = self.groups_list {
+            acceptor.set_groups_list(groups)?;
+        }
+
+        if let Some(ref callback) = self.psk_server_callback {
+            let callback = callback.0.clone();
+
+            // The acceptor prefers its own cipher order, so PSK clients get a PSK suite.
+            acceptor.set_cipher_list(crate::PSK_CIPHER_LIST)?;
+
+            acceptor.set_psk_server_callback(move |_, identity, psk| match callback(identity) {
+                Some(ref key) if key.len() <= psk.len() => {
+                    psk[..key.len()].copy_from_slice(key);
+                    Ok(key.len())
+                }
+                Some(_) => {
+                    debug!("PSK key too long");
+                    Ok(0)
+                }
+                None => Ok(0),
+            });
+        }
+
+        for cert in &self.root_certificates {
+            cert.add_to_store(acceptor.cert_store_mut())?;
+        }
+
+        for cert in &self.client_ca_names {
+            acceptor.add_client_ca(&cert.0)?;
+        }
+
+        if self.request_client_auth {
+            acceptor.set_verify(SslVerifyMode::PEER);
+            // Resuming a session fails without a context when client certificates are verified.
+            acceptor.set_session_id_context(b"opentls")?;
+        }
+
+        if let Some(ref key) = self.ticket_key {
+            set_ticket_key(&mut acceptor, key)?;
+        }
+
+        for callback in &self.openssl_callbacks {
+            (callback.0)(&mut acceptor)?;
+        }
 
         Ok(TlsAcceptor(acceptor.build()))
     }
 }
+
+fn set_ticket_key(acceptor: &mut SslAcceptorBuilder, key: &[u8]) -> crate::Result<()> {
+    let res = unsafe {
+        ffi::SSL_CTX_ctrl(
+            acceptor.as_ptr(),
+            SSL_CTRL_SET_TLSEXT_TICKET_KEYS,
+            key.len() as _,
+            key.as_ptr() as *mut c_void,
+        )
+    };
+
+    if res != 1 {
+        let err = io::Error::new(io::ErrorKind::InvalidInput, "invalid session ticket key length");
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+/// Adds `cert` to the chain of the certificate set last, unlike `add_extra_chain_cert`.
+fn add_chain_cert(acceptor: &mut SslAcceptorBuilder, cert: &X509Ref) -> Result<(), ErrorStack> {
+    // `SSL_CTX_add1_chain_cert` is a macro over `SSL_CTX_ctrl`.
+    let res = unsafe {
+        ffi::SSL_CTX_ctrl(
+            acceptor.as_ptr(),
+            ffi::SSL_CTRL_CHAIN_CERT,
+            1,
+            cert.as_ptr() as *mut c_void,
+        )
+    };
+
+    if res != 1 {
+        return Err(ErrorStack::get());
+    }
+
+    Ok(())
+}
@@ -1,11 +1,18 @@
 mod builder;
 
-use std::{fmt, io};
+use std::{
+    fmt, io,
+    net::TcpStream,
+    time::{Duration, Instant},
+};
 
 pub use builder::TlsConnectorBuilder;
-use openssl::ssl::{SslConnector, SslVerifyMode};
+use openssl::{
+    error::ErrorStack,
+    ssl::{ConnectConfiguration, SslConnector, SslMethod, SslStream, SslVerifyMode, StatusType},
+};
 
-use crate::{sync_io::TlsStream, HandshakeError, Protocol};
+use crate::{session::SessionCache, sync_io::TlsStream, HandshakeError, Host, Method, Protocol};
 
 /// A builder for client-side TLS connections.
 ///
@@ -30,6 +37,8 @@ use crate::{sync_io::TlsStream, HandshakeError, Protocol};
 pub struct TlsConnector {
     connector: SslConnector,
     use_sni: bool,
+    status_request: bool,
+    session_cache: Option<SessionCache>,
     accept_invalid_hostnames: bool,
     accept_invalid_certs: bool,
 }
@@ -39,6 +48,8 @@ impl fmt::Debug for TlsConnector {
         fmt.debug_struct("TlsConnector")
             // n.b. SslConnector is a newtype on SslContext which implements a noop Debug so it's omitted
             .field("use_sni", &self.use_sni)
+            .field("status_request", &self.status_request)
+            .field("session_cache", &self.session_cache.is_some())
             .field("accept_invalid_hostnames", &self.accept_invalid_hostnames)
             .field("accept_invalid_certs", &self.accept_invalid_certs)
             .finish()
@@ -53,22 +64,86 @@ impl TlsConnector {
 
     /// Returns a new builder for a `TlsConnector`.
     pub fn builder() -> TlsConnectorBuilder {
-        TlsConnectorBuilder {
+        #[allow(unused_mut)]
+        let mut builder = TlsConnectorBuilder {
+            method: Method(SslMethod::tls()),
             identity: None,
-            min_protocol: Some(Protocol::Tlsv10),
+            client_cert_chain: vec![],
+            min_protocol: Some(Protocol::Tlsv12),
             max_protocol: None,
             root_certificates: vec![],
+            ca_file: None,
+            cert_dirs: vec![],
+            crls: vec![],
             use_sni: true,
             accept_invalid_certs: false,
+            accept_invalid_certs_except_expiry: false,
+            verify_callback: None,
+            verify_mode: None,
             accept_invalid_hostnames: false,
             disable_built_in_roots: false,
+            use_system_cert_env_vars: true,
+            status_request: false,
+            session_cache: None,
+            disable_tickets: false,
+            read_ahead: false,
+            disable_compression: true,
+            #[cfg(have_ossl110h)]
+            disable_renegotiation: false,
+            allow_legacy_renegotiation: false,
+            psk_client_callback: None,
+            #[cfg(have_ossl111)]
+            groups_list: None,
+            #[cfg(have_ossl110)]
+            security_level: None,
+            #[cfg(have_ossl111)]
+            max_fragment_length: None,
+            #[cfg(have_ossl111)]
+            block_padding: 0,
+            #[cfg(have_ossl111)]
+            keylog_callback: None,
+            #[cfg(have_ossl111)]
+            use_sslkeylogfile: false,
+            #[cfg(have_ossl111)]
+            post_handshake_auth: false,
+        };
+
+        #[cfg(target_os = "android")]
+        builder.load_android_system_certs();
+
+        builder
+    }
+
+    /// Creates a `TlsConnector` from an OpenSSL connector configured by hand, e.g. when
+    /// migrating existing OpenSSL code.
+    ///
+    /// Bypasses `TlsConnectorBuilder` entirely, so only the given options apply on top of the
+    /// configuration of `connector`. The domain passed to `connect` is still used for SNI and
+    /// hostname verification, unless disabled here.
+    pub fn from_openssl(
+        connector: SslConnector,
+        use_sni: bool,
+        accept_invalid_hostnames: bool,
+        accept_invalid_certs: bool,
+    ) -> TlsConnector {
+        TlsConnector {
+            connector,
+            use_sni,
+            status_request: false,
+            session_cache: None,
+            accept_invalid_hostnames,
+            accept_invalid_certs,
         }
     }
 
     /// Initiates a TLS handshake.
     ///
     /// The provided domain will be used for both SNI and certificate hostname
-    /// validation.
+    /// validation. Like with the async `connect`, it can be given as anything converting
+    /// into a `Host`, such as a string or a socket address. An IP address, such as
+    /// `10.0.0.5` or `::1`, is instead matched
+    /// against the IP address entries of the certificate's subject alternative
+    /// names, and not sent with SNI.
     ///
     /// If the socket is nonblocking and a `WouldBlock` error is returned during
     /// the handshake, a `HandshakeError::WouldBlock` error will be returned
@@ -77,10 +152,128 @@ impl TlsConnector {
     ///
     /// The domain is ignored if both SNI and hostname verification are
     /// disabled.
-    pub fn connect<S>(&self, domain: &str, stream: S) -> Result<TlsStream<S>, HandshakeError<S>>
+    pub fn connect<S>(&self, domain: impl Into<Host>, stream: S) -> Result<TlsStream<S>, HandshakeError<S>>
     where
         S: io::Read + io::Write,
     {
+        let domain = domain.into();
+        Ok(TlsStream::new(
+            self.configure(domain.as_str())?.connect(domain.as_str(), stream)?,
+        ))
+    }
+
+    /// Initiates a TLS handshake like `connect`, failing with an I/O error of kind `TimedOut`
+    /// if it doesn't complete within `timeout`.
+    ///
+    /// Without a timeout, a peer accepting the TCP connection but never answering stalls the
+    /// handshake forever. The read and write timeouts of the stream are lowered to the time
+    /// left while the handshake runs, and restored once it completes. The stream must be in
+    /// blocking mode.
+    pub fn connect_timeout(
+        &self,
+        domain: impl Into<Host>,
+        stream: TcpStream,
+        timeout: Duration,
+    ) -> crate::Result<TlsStream<TcpStream>> {
+        let deadline = Instant::now() + timeout;
+        let timeouts = (stream.read_timeout()?, stream.write_timeout()?);
+
+        set_remaining_timeout(&stream, deadline)?;
+        let mut result = self.connect(domain, stream);
+
+        loop {
+            match result {
+                Ok(stream) => {
+                    stream.set_read_timeout(timeouts.0)?;
+                    stream.set_write_timeout(timeouts.1)?;
+                    return Ok(stream);
+                }
+                Err(HandshakeError::WouldBlock(mid)) => {
+                    set_remaining_timeout(mid.get_ref(), deadline)?;
+                    result = mid.handshake().map(TlsStream::new).map_err(HandshakeError::from);
+                }
+                // Windows reports a read running into the timeout as `TimedOut` instead of
+                // `WouldBlock`, failing the handshake.
+                Err(HandshakeError::Failure(_)) if Instant::now() >= deadline => return Err(timed_out().into()),
+                Err(HandshakeError::Failure(e)) => return Err(e),
+            }
+        }
+    }
+
+    /// Initiates a TLS handshake without verifying that the certificate matches `domain`,
+    /// for this connection only.
+    ///
+    /// The certificate chain is still validated, and the domain is still sent with SNI. Unlike
+    /// `TlsConnectorBuilder::danger_accept_invalid_hostnames`, other connections made with the
+    /// same connector are not affected.
+    ///
+    /// # Warning
+    ///
+    /// Any valid certificate for *any* site will be trusted for this connection. Only use this
+    /// for hosts authenticated otherwise, e.g. internal hosts with certificates of a dedicated
+    /// CA.
+    ///
+    /// The session cache is bypassed for this connection: no session is resumed, and the new
+    /// session isn't stored, so a later `connect` can't resume it without checking the hostname.
+    pub fn connect_no_hostname_check<S>(
+        &self,
+        domain: impl Into<Host>,
+        stream: S,
+    ) -> Result<TlsStream<S>, HandshakeError<S>>
+    where
+        S: io::Read + io::Write,
+    {
+        let domain = domain.into();
+        let ssl = self.configure_uncached()?.verify_hostname(false);
+        Ok(TlsStream::new(ssl.connect(domain.as_str(), stream)?))
+    }
+
+    /// Sets up a connection without starting the handshake.
+    ///
+    /// The handshake is performed with [`TlsStream::handshake`], or with the first read or
+    /// write, e.g. once the transport is ready. Until then the stream has no peer certificate
+    /// and hostname verification hasn't happened yet. Like `connect`, the handshake can be
+    /// resumed on a nonblocking stream.
+    pub fn setup<S>(&self, domain: impl Into<Host>, stream: S) -> crate::Result<TlsStream<S>>
+    where
+        S: io::Read + io::Write,
+    {
+        let domain = domain.into();
+        let mut ssl = self.configure(domain.as_str())?.into_ssl(domain.as_str())?;
+        ssl.set_connect_state();
+
+        Ok(TlsStream::new(SslStream::new(ssl, stream)?))
+    }
+
+    /// Starts a connection without completing the handshake, so TLS 1.3 early data can be
+    /// sent with [`TlsStream::write_early_data`].
+    ///
+    /// Early data can only be sent when resuming a session of a server accepting it, so
+    /// the session cache has to be enabled with `TlsConnectorBuilder::enable_session_cache`
+    /// and the same host connected to before. The handshake completes with the first read
+    /// or write, or with [`TlsStream::handshake`]. Until then the stream has no peer
+    /// certificate and hostname verification hasn't happened yet. Requires OpenSSL 1.1.1 or
+    /// newer.
+    #[cfg(have_ossl111)]
+    pub fn connect_early<S>(&self, domain: impl Into<Host>, stream: S) -> crate::Result<TlsStream<S>>
+    where
+        S: io::Read + io::Write,
+    {
+        self.setup(domain, stream)
+    }
+
+    fn configure(&self, domain: &str) -> Result<ConnectConfiguration, ErrorStack> {
+        let mut ssl = self.configure_uncached()?;
+
+        if let Some(ref cache) = self.session_cache {
+            cache.prepare(&mut ssl, domain)?;
+        }
+
+        Ok(ssl)
+    }
+
+    /// Configures a connection without resuming or storing sessions.
+    fn configure_uncached(&self) -> Result<ConnectConfiguration, ErrorStack> {
         let mut ssl = self
             .connector
             .configure()?
@@ -91,6 +284,26 @@ impl TlsConnector {
             ssl.set_verify(SslVerifyMode::NONE);
         }
 
-        Ok(TlsStream(ssl.connect(domain, stream)?))
+        if self.status_request {
+            ssl.set_status_type(StatusType::OCSP)?;
+        }
+
+        Ok(ssl)
+    }
+}
+
+/// Sets the read and write timeouts of `stream` to the time left until `deadline`.
+fn set_remaining_timeout(stream: &TcpStream, deadline: Instant) -> io::Result<()> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+
+    if remaining == Duration::from_secs(0) {
+        return Err(timed_out());
     }
+
+    stream.set_read_timeout(Some(remaining))?;
+    stream.set_write_timeout(Some(remaining))
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out")
 }
@@ -1,37 +1,153 @@
-use std::sync::Once;
+use std::{
+    fmt, fs, io,
+    os::raw::c_int,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use foreign_types::{ForeignType, ForeignTypeRef};
 use openssl::{
-    ssl::{SslConnector, SslMethod},
-    x509::store::X509StoreBuilder,
+    error::ErrorStack,
+    ssl::{SslConnector, SslMethod, SslOptions, SslVerifyMode},
+    x509::{
+        store::{X509StoreBuilder, X509StoreBuilderRef},
+        verify::X509VerifyFlags,
+        X509Crl, X509StoreContextRef, X509,
+    },
 };
+use openssl_sys as ffi;
+
+#[cfg(have_ossl111)]
+use openssl::ssl::SslConnectorBuilder;
+#[cfg(have_ossl111)]
+use std::{env, fs::OpenOptions, io::Write, sync::Mutex};
 
-use crate::{supported_protocols, sync_io::TlsConnector, Certificate, Identity, Protocol};
+use crate::{
+    session::{SessionCache, SessionCacheConfig},
+    supported_protocols,
+    sync_io::TlsConnector,
+    Certificate, Identity, MaxFragmentLength, Method, Protocol, SessionStore,
+};
+
+/// The directories of the Android system trust store, the Conscrypt APEX module first.
+const ANDROID_CERT_DIRS: [&str; 2] = ["/apex/com.android.conscrypt/cacerts", "/system/etc/security/cacerts"];
 
 /// A builder for `TlsConnector`s.
-#[derive(Debug)]
+///
+/// The builder can be cloned to derive several configurations from a common base.
+#[derive(Clone, Debug)]
 pub struct TlsConnectorBuilder {
+    pub(crate) method: Method,
     pub(crate) identity: Option<Identity>,
+    pub(crate) client_cert_chain: Vec<Certificate>,
     pub(crate) min_protocol: Option<Protocol>,
     pub(crate) max_protocol: Option<Protocol>,
     pub(crate) root_certificates: Vec<Certificate>,
+    pub(crate) ca_file: Option<PathBuf>,
+    pub(crate) cert_dirs: Vec<PathBuf>,
+    pub(crate) crls: Vec<Vec<u8>>,
     pub(crate) accept_invalid_certs: bool,
+    pub(crate) accept_invalid_certs_except_expiry: bool,
+    pub(crate) verify_callback: Option<VerifyCallback>,
+    pub(crate) verify_mode: Option<SslVerifyMode>,
     pub(crate) accept_invalid_hostnames: bool,
     pub(crate) use_sni: bool,
     pub(crate) disable_built_in_roots: bool,
+    pub(crate) use_system_cert_env_vars: bool,
+    pub(crate) status_request: bool,
+    pub(crate) session_cache: Option<SessionCacheConfig>,
+    pub(crate) disable_tickets: bool,
+    pub(crate) read_ahead: bool,
+    pub(crate) disable_compression: bool,
+    #[cfg(have_ossl110h)]
+    pub(crate) disable_renegotiation: bool,
+    pub(crate) allow_legacy_renegotiation: bool,
+    #[cfg(have_ossl111)]
+    pub(crate) groups_list: Option<String>,
+    #[cfg(have_ossl110)]
+    pub(crate) security_level: Option<u32>,
+    #[cfg(have_ossl111)]
+    pub(crate) max_fragment_length: Option<MaxFragmentLength>,
+    #[cfg(have_ossl111)]
+    pub(crate) block_padding: usize,
+    pub(crate) psk_client_callback: Option<PskClientCallback>,
+    #[cfg(have_ossl111)]
+    pub(crate) keylog_callback: Option<KeylogCallback>,
+    #[cfg(have_ossl111)]
+    pub(crate) use_sslkeylogfile: bool,
+    #[cfg(have_ossl111)]
+    pub(crate) post_handshake_auth: bool,
+}
+
+/// A user-provided callback returning the PSK identity and key for an identity hint.
+#[derive(Clone)]
+pub(crate) struct PskClientCallback(Arc<PskClientFn>);
+
+type PskClientFn = dyn Fn(Option<&[u8]>) -> Option<(Vec<u8>, Vec<u8>)> + Send + Sync;
+
+impl fmt::Debug for PskClientCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PskClientCallback").finish()
+    }
+}
+
+/// A user-provided callback deciding on the verification of each certificate of the chain.
+#[derive(Clone)]
+pub(crate) struct VerifyCallback(Arc<VerifyFn>);
+
+type VerifyFn = dyn Fn(bool, &mut X509StoreContextRef) -> bool + Send + Sync;
+
+impl fmt::Debug for VerifyCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifyCallback").finish()
+    }
+}
+
+/// A user-provided callback receiving TLS key log lines.
+#[cfg(have_ossl111)]
+#[derive(Clone)]
+pub(crate) struct KeylogCallback(Arc<dyn Fn(&str) + Send + Sync>);
+
+#[cfg(have_ossl111)]
+impl fmt::Debug for KeylogCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeylogCallback").finish()
+    }
 }
 
 impl TlsConnectorBuilder {
+    /// Sets the OpenSSL method the connector is created with, e.g. `SslMethod::tls_client()`.
+    ///
+    /// Defaults to `SslMethod::tls()`, negotiating the highest TLS version both sides support.
+    pub fn with_method(&mut self, method: SslMethod) -> &mut TlsConnectorBuilder {
+        self.method = Method(method);
+        self
+    }
+
     /// Sets the identity to be used for client certificate authentication.
     pub fn identity(&mut self, identity: Identity) -> &mut TlsConnectorBuilder {
         self.identity = Some(identity);
         self
     }
 
+    /// Adds intermediate certificates to send along with the certificate of the identity, for
+    /// chains distributed separately from the leaf certificate and key.
+    ///
+    /// The certificates are expected in the order `Identity::from_pkcs12` keeps the chain of an
+    /// archive, from the root towards the issuer of the leaf certificate, and are added in
+    /// reverse like it, after the chain of the identity itself. `build` fails if no identity is
+    /// set, as the chain would never be sent.
+    pub fn add_client_cert_chain(&mut self, certs: Vec<Certificate>) -> &mut TlsConnectorBuilder {
+        self.client_cert_chain.extend(certs);
+        self
+    }
+
     /// Sets the minimum supported protocol version.
     ///
     /// A value of `None` enables support for the oldest protocols supported by the implementation.
     ///
-    /// Defaults to `Some(Protocol::Tlsv10)`.
+    /// Defaults to `Some(Protocol::Tlsv12)`. TLS 1.0 and 1.1 are deprecated and have to be
+    /// enabled explicitly, e.g. for legacy peers.
     pub fn min_protocol_version(&mut self, protocol: Option<Protocol>) -> &mut TlsConnectorBuilder {
         self.min_protocol = protocol;
         self
@@ -52,12 +168,104 @@ impl TlsConnectorBuilder {
     /// The connector will use the system's trust root by default. This method can be used to add
     /// to that set when communicating with servers not trusted by the system.
     ///
+    /// Building the connector fails if OpenSSL can't add the certificate to its store.
+    ///
     /// Defaults to an empty set.
     pub fn add_root_certificate(&mut self, cert: Certificate) -> &mut TlsConnectorBuilder {
         self.root_certificates.push(cert);
         self
     }
 
+    /// Adds several certificates to the set of roots that the connector will trust.
+    ///
+    /// Equivalent to calling `add_root_certificate` for each of them.
+    pub fn add_root_certificates<I>(&mut self, certs: I) -> &mut TlsConnectorBuilder
+    where
+        I: IntoIterator<Item = Certificate>,
+    {
+        self.root_certificates.extend(certs);
+        self
+    }
+
+    /// Adds the certificates of the operating system's trust store to the set of roots, as
+    /// loaded by `rustls-native-certs`.
+    ///
+    /// Useful where OpenSSL doesn't find the system roots on its own, e.g. with the `vendored`
+    /// feature, or on Windows and macOS, whose trust stores OpenSSL doesn't read. Certificates
+    /// OpenSSL can't parse are skipped. Fails if no certificate could be loaded at all.
+    #[cfg(feature = "native-certs")]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "native-certs")))]
+    pub fn with_native_certs(&mut self) -> crate::Result<&mut TlsConnectorBuilder> {
+        let native = rustls_native_certs::load_native_certs();
+
+        if native.certs.is_empty() {
+            let err = match native.errors.into_iter().next() {
+                Some(err) => io::Error::other(err),
+                None => io::Error::new(io::ErrorKind::NotFound, "no native root certificates found"),
+            };
+
+            return Err(err.into());
+        }
+
+        for cert in &native.certs {
+            match Certificate::from_der(cert) {
+                Ok(cert) => self.root_certificates.push(cert),
+                Err(err) => debug!("native root certificate error: {:?}", err),
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Adds every PEM-encoded certificate of a directory to the set of roots, e.g. a trust store
+    /// OpenSSL doesn't know about.
+    ///
+    /// The directory is read when the connector is built. Unlike `set_ca_file`, a missing
+    /// directory and files which aren't certificates are skipped.
+    pub fn add_root_certificate_dir(&mut self, dir: &Path) -> &mut TlsConnectorBuilder {
+        if !self.cert_dirs.iter().any(|d| d == dir) {
+            self.cert_dirs.push(dir.to_owned());
+        }
+
+        self
+    }
+
+    /// Adds the certificates of the Android system trust store to the set of roots, from both
+    /// the Conscrypt APEX module of Android 14 and newer and `/system/etc/security/cacerts`.
+    ///
+    /// Done by default on Android. The directories don't exist on other targets, where this is
+    /// a no-op.
+    pub fn load_android_system_certs(&mut self) -> &mut TlsConnectorBuilder {
+        for dir in ANDROID_CERT_DIRS {
+            self.add_root_certificate_dir(Path::new(dir));
+        }
+
+        self
+    }
+
+    /// Sets a file of PEM-encoded certificates for the connector to trust, such as a CA bundle
+    /// pointed to by `SSL_CERT_FILE`.
+    ///
+    /// The file is loaded by OpenSSL when the connector is built, in addition to the system's
+    /// trust roots unless `disable_built_in_roots` is set. Fails if the file doesn't exist.
+    pub fn set_ca_file(&mut self, path: &Path) -> crate::Result<&mut TlsConnectorBuilder> {
+        fs::metadata(path)?;
+        self.ca_file = Some(path.to_owned());
+        Ok(self)
+    }
+
+    /// Adds a PEM-encoded certificate revocation list, and enables checking the server
+    /// certificate against the CRLs of its issuer.
+    ///
+    /// Once a CRL is added, the handshake fails with a verification error if the server
+    /// certificate is revoked, or if no CRL of its issuer was added. Intermediate certificates
+    /// are not checked.
+    pub fn add_crl(&mut self, crl_pem: &[u8]) -> crate::Result<&mut TlsConnectorBuilder> {
+        let crl = X509Crl::from_pem(crl_pem)?;
+        self.crls.push(crl.to_der()?);
+        Ok(self)
+    }
+
     /// Controls the use of built-in system certificates during certificate validation.
     ///
     /// Defaults to `false` -- built-in system certs will be used.
@@ -66,6 +274,21 @@ impl TlsConnectorBuilder {
         self
     }
 
+    /// Controls looking up the system certificate locations with `openssl-probe`, for the
+    /// built-in roots.
+    ///
+    /// OpenSSL reads the built-in roots from the `SSL_CERT_FILE` and `SSL_CERT_DIR` environment
+    /// variables, or else from the paths it was configured with, which don't match the system
+    /// with the `vendored` feature. If enabled, the variables not set already are set to the
+    /// locations found on the system, such as `/etc/ssl/certs/ca-certificates.crt`. This
+    /// happens once per process, and affects every later use of OpenSSL.
+    ///
+    /// Defaults to `true`.
+    pub fn use_system_cert_env_vars(&mut self, enable: bool) -> &mut TlsConnectorBuilder {
+        self.use_system_cert_env_vars = enable;
+        self
+    }
+
     /// Controls the use of certificate validation.
     ///
     /// Defaults to `false`.
@@ -80,9 +303,87 @@ impl TlsConnectorBuilder {
         self
     }
 
+    /// Accepts invalid certificates, e.g. self-signed ones or issued by an unknown CA, as long
+    /// as they are not expired or not yet valid.
+    ///
+    /// This is meant for peers authenticated otherwise, such as by pinning the fingerprint of
+    /// their certificate with `TlsStream::peer_certificate_fingerprint`. The hostname is still
+    /// verified unless `danger_accept_invalid_hostnames` is set.
+    ///
+    /// # Warning
+    ///
+    /// Without further checks, *any* certificate for the host which is within its validity period
+    /// will be trusted, including revoked ones.
+    pub fn accept_invalid_certs_except_expiry(&mut self) -> &mut TlsConnectorBuilder {
+        self.accept_invalid_certs_except_expiry = true;
+        self
+    }
+
+    /// Sets the OpenSSL verification mode, a lower-level alternative to the other verification
+    /// settings.
+    ///
+    /// With `SslVerifyMode::NONE`, the handshake doesn't fail on an invalid certificate chain
+    /// or hostname, and the verification result is only available from the stream afterwards.
+    /// Flags only meaningful to servers, such as `FAIL_IF_NO_PEER_CERT`, are ignored by
+    /// OpenSSL. `danger_accept_invalid_certs` takes precedence over the mode, and a callback
+    /// set with `set_verify_callback` is called with any mode but `NONE`.
+    ///
+    /// Defaults to `SslVerifyMode::PEER`.
+    pub fn set_verify_mode(&mut self, mode: SslVerifyMode) -> &mut TlsConnectorBuilder {
+        self.verify_mode = Some(mode);
+        self
+    }
+
+    /// Sets a callback deciding on the verification of each certificate of the server's chain,
+    /// e.g. to pin certificates.
+    ///
+    /// The callback is called for every certificate from the root towards the leaf, with
+    /// whether OpenSSL's own verification passed, and returns whether to accept the
+    /// certificate. The certificate, its depth in the chain and the error are available from
+    /// the `X509StoreContextRef`. With `accept_invalid_certs_except_expiry`, the callback is
+    /// told the result of that check instead. The callback isn't called with
+    /// `danger_accept_invalid_certs`.
+    ///
+    /// # Examples
+    ///
+    /// Accepting only a server certificate with a known SHA-256 fingerprint:
+    ///
+    /// ```rust
+    /// use opentls::{sync_io::TlsConnector, MessageDigest};
+    ///
+    /// let pinned: Vec<u8> = vec![/* the fingerprint */];
+    ///
+    /// let mut builder = TlsConnector::builder();
+    /// builder.set_verify_callback(move |preverified, ctx| {
+    ///     if ctx.error_depth() != 0 {
+    ///         return preverified;
+    ///     }
+    ///
+    ///     match ctx.current_cert().map(|cert| cert.digest(MessageDigest::sha256())) {
+    ///         Some(Ok(digest)) => preverified && *digest == *pinned,
+    ///         _ => false,
+    ///     }
+    /// });
+    /// ```
+    pub fn set_verify_callback<F>(&mut self, callback: F) -> &mut TlsConnectorBuilder
+    where
+        F: Fn(bool, &mut X509StoreContextRef) -> bool + Send + Sync + 'static,
+    {
+        self.verify_callback = Some(VerifyCallback(Arc::new(callback)));
+        self
+    }
+
     /// Controls the use of Server Name Indication (SNI).
     ///
-    /// Defaults to `true`.
+    /// SNI and hostname verification are independent of each other:
+    ///
+    /// * with both enabled, the domain is sent with SNI and verified against the certificate;
+    /// * with SNI disabled, the domain is still verified, only the server doesn't learn it;
+    /// * with `danger_accept_invalid_hostnames`, the domain is still sent with SNI, only the
+    ///   certificate isn't verified against it;
+    /// * with both disabled, the domain is ignored.
+    ///
+    /// IP addresses are never sent with SNI. Defaults to `true`.
     pub fn use_sni(&mut self, use_sni: bool) -> &mut TlsConnectorBuilder {
         self.use_sni = use_sni;
         self
@@ -90,7 +391,8 @@ impl TlsConnectorBuilder {
 
     /// Controls the use of hostname verification.
     ///
-    /// Defaults to `false`.
+    /// The domain is still sent with SNI unless it's disabled with `use_sni`. Defaults to
+    /// `false`.
     ///
     /// # Warning
     ///
@@ -102,12 +404,245 @@ impl TlsConnectorBuilder {
         self
     }
 
+    /// Controls requesting a stapled OCSP response from the server.
+    ///
+    /// The response, if the server sends one, is available from `TlsStream::ocsp_response` after
+    /// the handshake. It is not validated by the connector.
+    ///
+    /// Defaults to `false`.
+    pub fn set_status_request(&mut self, status_request: bool) -> &mut TlsConnectorBuilder {
+        self.status_request = status_request;
+        self
+    }
+
+    /// Controls caching sessions in memory to resume them on later connections to the same host.
+    ///
+    /// Resuming a session skips most of the handshake, which saves a round trip and the costly
+    /// key exchange. `TlsStream::session_reused` tells whether a connection was resumed. The cache
+    /// is shared by all connections of the connectors built from this builder, a clone of the
+    /// builder gets a cache of its own. OpenSSL invalidates the session of a connection dropped
+    /// without `TlsStream::shutdown`, it is removed from the cache.
+    ///
+    /// A resumed session skips the certificate verification, so connectors not verifying the
+    /// server fully, with `danger_accept_invalid_certs`, `danger_accept_invalid_hostnames`,
+    /// `accept_invalid_certs_except_expiry`, a verify callback or a verify mode without `PEER`,
+    /// never store or resume sessions.
+    ///
+    /// Defaults to `false`.
+    pub fn enable_session_cache(&mut self, enable: bool) -> &mut TlsConnectorBuilder {
+        self.session_cache = if enable {
+            Some(SessionCacheConfig::Memory(SessionCache::memory()))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Sets a store to cache sessions in, to resume them on later connections to the same host.
+    ///
+    /// Unlike `enable_session_cache`, which keeps the sessions in memory, this allows sharing
+    /// sessions between processes. The store is shared by the clones of this builder.
+    ///
+    /// # Warning
+    ///
+    /// Sessions are stored by host name only. A store must only be shared by connectors with
+    /// the same trust roots, verification settings and client identity, see `SessionStore`.
+    /// As with `enable_session_cache`, connectors not verifying the server fully never store
+    /// or resume sessions.
+    pub fn set_session_store(&mut self, store: Arc<dyn SessionStore>) -> &mut TlsConnectorBuilder {
+        self.session_cache = Some(SessionCacheConfig::Store(SessionCache::store(store)));
+        self
+    }
+
+    /// Controls requesting session tickets from the server.
+    ///
+    /// Only affects TLS 1.2 and older, TLS 1.3 servers issue tickets regardless.
+    ///
+    /// Defaults to `false`.
+    pub fn disable_tickets(&mut self, disable: bool) -> &mut TlsConnectorBuilder {
+        self.disable_tickets = disable;
+        self
+    }
+
+    /// Controls OpenSSL's read-ahead, reading as many bytes from the stream as are
+    /// available instead of one record at a time.
+    ///
+    /// This saves system calls on bulk transfers, at the cost of buffering up to a full
+    /// record more inside OpenSSL. The BIO buffer size itself can't be tuned, since the
+    /// stream is read through a custom BIO.
+    ///
+    /// Defaults to `false`.
+    pub fn set_read_ahead(&mut self, read_ahead: bool) -> &mut TlsConnectorBuilder {
+        self.read_ahead = read_ahead;
+        self
+    }
+
+    /// Controls TLS compression, which exposes the connection to CRIME-style attacks.
+    ///
+    /// Compression is only available if OpenSSL was built with zlib.
+    ///
+    /// Defaults to `true`.
+    pub fn disable_compression(&mut self, disable: bool) -> &mut TlsConnectorBuilder {
+        self.disable_compression = disable;
+        self
+    }
+
+    /// Controls refusing renegotiation requests of the server.
+    ///
+    /// Defaults to `false`. Requires OpenSSL 1.1.0h or newer.
+    #[cfg(have_ossl110h)]
+    pub fn disable_renegotiation(&mut self, disable: bool) -> &mut TlsConnectorBuilder {
+        self.disable_renegotiation = disable;
+        self
+    }
+
+    /// Controls connecting to and renegotiating with servers not supporting secure
+    /// renegotiation (RFC 5746), such as old appliances.
+    ///
+    /// Renegotiation requests of servers supporting it are always completed transparently
+    /// within reads and writes, unless `disable_renegotiation` is set. Otherwise, connecting
+    /// to a legacy server fails, and so does the read or write during which it requests a
+    /// renegotiation, with an `Error::Renegotiation` wrapped in the I/O error.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Warning
+    ///
+    /// Legacy renegotiation allows an attacker to inject data at the start of the connection.
+    /// Only enable this for peers that can't be upgraded.
+    pub fn allow_legacy_renegotiation(&mut self, allow: bool) -> &mut TlsConnectorBuilder {
+        self.allow_legacy_renegotiation = allow;
+        self
+    }
+
+    /// Sets the supported key exchange groups in order of preference, e.g. `"X25519:P-256"`.
+    ///
+    /// Building the connector fails if the list contains a group unknown to OpenSSL. Defaults to
+    /// the OpenSSL defaults. Requires OpenSSL 1.1.1 or newer.
+    #[cfg(have_ossl111)]
+    pub fn set_groups_list(&mut self, groups: &str) -> &mut TlsConnectorBuilder {
+        self.groups_list = Some(groups.into());
+        self
+    }
+
+    /// Sets the OpenSSL security level, from 0 to 5.
+    ///
+    /// Higher levels enforce larger minimum key sizes and disable weak algorithms, e.g. level 3
+    /// requires 128-bit security and rejects RSA keys shorter than 3072 bits.
+    ///
+    /// Defaults to the level OpenSSL was configured with. Requires OpenSSL 1.1.0 or newer.
+    #[cfg(have_ossl110)]
+    pub fn set_security_level(&mut self, level: u32) -> &mut TlsConnectorBuilder {
+        self.security_level = Some(level);
+        self
+    }
+
+    /// Requests a smaller maximum TLS record size from the server with the max_fragment_length
+    /// extension, e.g. to reduce memory pressure on constrained peers.
+    ///
+    /// The extension is negotiated in TLS 1.3 as well, with the server's answer in its
+    /// EncryptedExtensions. The handshake fails if the server answers with a different length.
+    /// Servers ignoring the extension keep the default of 16384 bytes. Fails with an
+    /// `Unsupported` error if OpenSSL is older than 1.1.1.
+    pub fn set_max_fragment_length(&mut self, len: MaxFragmentLength) -> crate::Result<&mut TlsConnectorBuilder> {
+        #[cfg(have_ossl111)]
+        {
+            self.max_fragment_length = Some(len);
+            Ok(self)
+        }
+
+        #[cfg(not(have_ossl111))]
+        {
+            let _ = len;
+            Err(io::Error::new(io::ErrorKind::Unsupported, "max_fragment_length requires OpenSSL 1.1.1").into())
+        }
+    }
+
+    /// Pads TLS 1.3 records to a multiple of `size` bytes, hiding the exact length of the data
+    /// sent from traffic analysis.
+    ///
+    /// Building the connector fails if `size` exceeds the maximum record size of 16384 bytes.
+    /// Defaults to `0`, disabling padding. Requires OpenSSL 1.1.1 or newer.
+    #[cfg(have_ossl111)]
+    pub fn set_block_padding(&mut self, size: usize) -> &mut TlsConnectorBuilder {
+        self.block_padding = size;
+        self
+    }
+
+    /// Sets a callback providing a pre-shared key (PSK) to authenticate with instead of
+    /// certificates.
+    ///
+    /// The callback receives the identity hint of the server, if it sent one, and returns the
+    /// PSK identity and key to use, or `None` to abort the handshake. The identity can't contain
+    /// NUL bytes.
+    ///
+    /// The TLS 1.2 cipher suites authenticated with a PSK are enabled, in which case the server
+    /// doesn't send a certificate. With TLS 1.3, the PSK is used with a `SHA-256` cipher suite.
+    pub fn set_psk_client_callback<F>(&mut self, callback: F) -> &mut TlsConnectorBuilder
+    where
+        F: Fn(Option<&[u8]>) -> Option<(Vec<u8>, Vec<u8>)> + Send + Sync + 'static,
+    {
+        self.psk_client_callback = Some(PskClientCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Sets a callback receiving the TLS key material of every connection, one line at a time
+    /// in the NSS key log format.
+    ///
+    /// The lines can be written to a file and loaded into Wireshark to decrypt captured
+    /// traffic. Requires OpenSSL 1.1.1 or newer.
+    ///
+    /// # Warning
+    ///
+    /// Anyone in possession of the key log can decrypt the traffic of the logged connections.
+    /// Only use this for debugging.
+    #[cfg(have_ossl111)]
+    pub fn set_keylog_callback<F>(&mut self, callback: F) -> &mut TlsConnectorBuilder
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.keylog_callback = Some(KeylogCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Controls appending the TLS key material to the file named by the `SSLKEYLOGFILE`
+    /// environment variable, if it is set.
+    ///
+    /// Defaults to `false`. Requires OpenSSL 1.1.1 or newer.
+    ///
+    /// # Warning
+    ///
+    /// Anyone in possession of the key log can decrypt the traffic of the logged connections.
+    /// Only use this for debugging.
+    #[cfg(have_ossl111)]
+    pub fn use_sslkeylogfile(&mut self, enable: bool) -> &mut TlsConnectorBuilder {
+        self.use_sslkeylogfile = enable;
+        self
+    }
+
+    /// Allows the server to request a client certificate after a TLS 1.3 handshake, e.g. with
+    /// `TlsStream::request_client_auth`.
+    ///
+    /// The certificate of the identity is sent if the server asks for one. Requires OpenSSL
+    /// 1.1.1 or newer.
+    #[cfg(have_ossl111)]
+    pub fn enable_post_handshake_auth(&mut self) -> &mut TlsConnectorBuilder {
+        self.post_handshake_auth = true;
+        self
+    }
+
     /// Creates a new `TlsConnector`.
     pub fn build(&self) -> crate::Result<TlsConnector> {
-        static ONCE: Once = Once::new();
-        ONCE.call_once(openssl_probe::init_ssl_cert_env_vars);
+        if self.use_system_cert_env_vars {
+            crate::init_openssl_env();
+        }
+
+        let mut connector = SslConnector::builder(self.method.0)?;
 
-        let mut connector = SslConnector::builder(SslMethod::tls())?;
+        #[cfg(have_ossl110)]
+        if let Some(level) = self.security_level {
+            connector.set_security_level(level);
+        }
 
         if let Some(ref identity) = self.identity {
             connector.set_certificate(&identity.cert)?;
@@ -116,28 +651,273 @@ impl TlsConnectorBuilder {
             for cert in identity.chain.iter().rev() {
                 connector.add_extra_chain_cert(cert.to_owned())?;
             }
+
+            for cert in self.client_cert_chain.iter().rev() {
+                connector.add_extra_chain_cert(cert.0.clone())?;
+            }
+        } else if !self.client_cert_chain.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "client certificate chain without an identity",
+            )
+            .into());
         }
 
         supported_protocols(self.min_protocol, self.max_protocol, &mut connector)?;
 
+        if self.disable_tickets {
+            connector.set_options(SslOptions::NO_TICKET);
+        }
+
+        if self.read_ahead {
+            connector.set_read_ahead(true);
+        }
+
+        // Compression is disabled by `SslConnector` already.
+        if !self.disable_compression {
+            connector.clear_options(SslOptions::NO_COMPRESSION);
+        }
+
+        #[cfg(have_ossl110h)]
+        if self.disable_renegotiation {
+            connector.set_options(SslOptions::NO_RENEGOTIATION);
+        }
+
+        if self.allow_legacy_renegotiation {
+            // `LEGACY_SERVER_CONNECT` is not exposed by `openssl`.
+            let legacy_server_connect = SslOptions::from_bits_retain(ffi::SSL_OP_LEGACY_SERVER_CONNECT);
+            connector.set_options(legacy_server_connect | SslOptions::ALLOW_UNSAFE_LEGACY_RENEGOTIATION);
+        }
+
+        #[cfg(have_ossl111)]
+        if let Some(ref groups) = self.groups_list {
+            connector.set_groups_list(groups)?;
+        }
+
+        #[cfg(have_ossl111)]
+        if let Some(len) = self.max_fragment_length {
+            set_max_fragment_length(&mut connector, len)?;
+        }
+
+        #[cfg(have_ossl111)]
+        if self.block_padding > 0 && unsafe { SSL_CTX_set_block_padding(connector.as_ptr(), self.block_padding) } != 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "block padding larger than a record").into());
+        }
+
         if self.disable_built_in_roots {
             connector.set_cert_store(X509StoreBuilder::new()?.build());
         }
 
+        if let Some(ref path) = self.ca_file {
+            connector.set_ca_file(path)?;
+        }
+
         for cert in &self.root_certificates {
-            if let Err(err) = connector.cert_store_mut().add_cert(cert.0.clone()) {
-                debug!("add_cert error: {:?}", err);
+            cert.add_to_store(connector.cert_store_mut())?;
+        }
+
+        for dir in &self.cert_dirs {
+            add_root_certificate_dir(connector.cert_store_mut(), dir);
+        }
+
+        if !self.crls.is_empty() {
+            let store = connector.cert_store_mut();
+
+            for crl in &self.crls {
+                add_crl(store, &X509Crl::from_der(crl)?)?;
             }
+
+            store.set_flags(X509VerifyFlags::CRL_CHECK)?;
         }
 
-        #[cfg(target_os = "android")]
-        crate::load_android_root_certs(&mut connector)?;
+        if self.verify_callback.is_some() || self.accept_invalid_certs_except_expiry {
+            let callback = self.verify_callback.clone();
+            let except_expiry = self.accept_invalid_certs_except_expiry;
+
+            let mode = self.verify_mode.unwrap_or(SslVerifyMode::PEER);
+
+            connector.set_verify_callback(mode, move |mut preverified, ctx| {
+                if except_expiry {
+                    preverified = verify_expiry_only(preverified, ctx);
+                }
+
+                match callback {
+                    Some(VerifyCallback(ref callback)) => callback(preverified, ctx),
+                    None => preverified,
+                }
+            });
+        } else if let Some(mode) = self.verify_mode {
+            connector.set_verify(mode);
+        }
+
+        if let Some(ref callback) = self.psk_client_callback {
+            let callback = callback.0.clone();
+
+            // The default cipher list of the connector excludes the PSK suites.
+            connector.set_cipher_list(crate::PSK_CIPHER_LIST)?;
+
+            connector.set_psk_client_callback(move |_, hint, identity, psk| {
+                let (id, key) = match callback(hint) {
+                    Some(credentials) => credentials,
+                    None => return Ok(0),
+                };
+
+                // The identity is a C string, the key is returned by length.
+                if id.len() >= identity.len() || id.contains(&0) || key.len() > psk.len() {
+                    debug!("PSK identity or key too long");
+                    return Ok(0);
+                }
+
+                identity[..id.len()].copy_from_slice(&id);
+                identity[id.len()] = 0;
+                psk[..key.len()].copy_from_slice(&key);
+
+                Ok(key.len())
+            });
+        }
+
+        #[cfg(have_ossl111)]
+        self.set_keylog(&mut connector);
+
+        #[cfg(have_ossl111)]
+        if self.post_handshake_auth {
+            unsafe { ffi::SSL_CTX_set_post_handshake_auth(connector.as_ptr(), 1) };
+        }
+
+        // Resumed sessions aren't verified again, a session of a lenient connector must not be
+        // resumed by a strict one sharing the store.
+        let session_cache = match self.session_cache {
+            Some(ref config) if self.verifies_peer_fully() => Some(config.cache().clone()),
+            _ => None,
+        };
+
+        if let Some(ref cache) = session_cache {
+            cache.install(&mut connector)?;
+        }
 
         Ok(TlsConnector {
             connector: connector.build(),
             use_sni: self.use_sni,
+            status_request: self.status_request,
+            session_cache,
             accept_invalid_hostnames: self.accept_invalid_hostnames,
             accept_invalid_certs: self.accept_invalid_certs,
         })
     }
+
+    /// Whether the connector verifies the certificate and the hostname of the server the default
+    /// way.
+    fn verifies_peer_fully(&self) -> bool {
+        !self.accept_invalid_certs
+            && !self.accept_invalid_hostnames
+            && !self.accept_invalid_certs_except_expiry
+            && self.verify_callback.is_none()
+            && self
+                .verify_mode
+                .unwrap_or(SslVerifyMode::PEER)
+                .contains(SslVerifyMode::PEER)
+    }
+
+    #[cfg(have_ossl111)]
+    fn set_keylog(&self, connector: &mut SslConnectorBuilder) {
+        let file = match env::var_os("SSLKEYLOGFILE") {
+            Some(path) if self.use_sslkeylogfile => match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => Some(Mutex::new(file)),
+                Err(err) => {
+                    debug!("SSLKEYLOGFILE open error: {:?}", err);
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let callback = self.keylog_callback.clone();
+
+        if file.is_none() && callback.is_none() {
+            return;
+        }
+
+        connector.set_keylog_callback(move |_, line| {
+            if let Some(ref file) = file {
+                if let Err(err) = writeln!(file.lock().unwrap(), "{}", line) {
+                    debug!("SSLKEYLOGFILE write error: {:?}", err);
+                }
+            }
+
+            if let Some(KeylogCallback(ref callback)) = callback {
+                callback(line);
+            }
+        });
+    }
+}
+
+/// Ignores every verification error except for the validity period and hostname of the
+/// certificates.
+fn verify_expiry_only(preverified: bool, ctx: &mut X509StoreContextRef) -> bool {
+    preverified
+        || !matches!(
+            ctx.error().as_raw(),
+            ffi::X509_V_ERR_CERT_NOT_YET_VALID
+                | ffi::X509_V_ERR_CERT_HAS_EXPIRED
+                | ffi::X509_V_ERR_ERROR_IN_CERT_NOT_BEFORE_FIELD
+                | ffi::X509_V_ERR_ERROR_IN_CERT_NOT_AFTER_FIELD
+                | ffi::X509_V_ERR_HOSTNAME_MISMATCH
+                | ffi::X509_V_ERR_IP_ADDRESS_MISMATCH
+        )
+}
+
+extern "C" {
+    // Not exposed by `openssl-sys`.
+    fn X509_STORE_add_crl(store: *mut ffi::X509_STORE, crl: *mut ffi::X509_CRL) -> c_int;
+    #[cfg(have_ossl111)]
+    fn SSL_CTX_set_tlsext_max_fragment_length(ctx: *mut ffi::SSL_CTX, mode: u8) -> c_int;
+    #[cfg(have_ossl111)]
+    fn SSL_CTX_set_block_padding(ctx: *mut ffi::SSL_CTX, block_size: usize) -> c_int;
+}
+
+#[cfg(have_ossl111)]
+fn set_max_fragment_length(connector: &mut SslConnectorBuilder, len: MaxFragmentLength) -> Result<(), ErrorStack> {
+    // The TLSEXT_max_fragment_length_* codes of RFC 6066.
+    let mode = match len {
+        MaxFragmentLength::Bytes512 => 1,
+        MaxFragmentLength::Bytes1024 => 2,
+        MaxFragmentLength::Bytes2048 => 3,
+        MaxFragmentLength::Bytes4096 => 4,
+    };
+
+    if unsafe { SSL_CTX_set_tlsext_max_fragment_length(connector.as_ptr(), mode) } != 1 {
+        return Err(ErrorStack::get());
+    }
+
+    Ok(())
+}
+
+fn add_root_certificate_dir(store: &mut X509StoreBuilderRef, dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            debug!("root certificate directory error: {:?}", err);
+            return;
+        }
+    };
+
+    let certs = entries
+        .filter_map(|r| r.ok())
+        .filter_map(|e| fs::read(e.path()).ok())
+        .filter_map(|b| X509::from_pem(&b).ok());
+
+    for cert in certs {
+        if let Err(err) = store.add_cert(cert) {
+            debug!("root certificate directory error: {:?}", err);
+        }
+    }
+}
+
+fn add_crl(store: &mut X509StoreBuilderRef, crl: &X509Crl) -> Result<(), ErrorStack> {
+    // The store takes its own reference to the CRL.
+    if unsafe { X509_STORE_add_crl(store.as_ptr(), crl.as_ptr()) } <= 0 {
+        return Err(ErrorStack::get());
+    }
+
+    Ok(())
 }
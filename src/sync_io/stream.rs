@@ -1,9 +1,50 @@
-use crate::Certificate;
-use openssl::{hash::MessageDigest, nid::Nid, ssl};
-use std::{fmt, io};
+use crate::{Certificate, ConnectionInfo, HandshakeError};
+use openssl::{asn1::Asn1Time, hash::MessageDigest, nid::Nid, ssl};
+use std::{cmp::Ordering, fmt, io, net::TcpStream, os::raw::c_int, time::Duration};
+
+#[cfg(have_ossl111)]
+use foreign_types::ForeignTypeRef;
+#[cfg(have_ossl111)]
+use openssl::error::ErrorStack;
+#[cfg(have_ossl111)]
+use openssl_sys as ffi;
+#[cfg(have_ossl320)]
+use std::ffi::CStr;
+#[cfg(all(have_ossl300, not(have_ossl320)))]
+use std::ptr;
+
+#[cfg(have_ossl111)]
+extern "C" {
+    // Not exposed by `openssl-sys`.
+    fn SSL_verify_client_post_handshake(ssl: *mut ffi::SSL) -> c_int;
+    fn SSL_get_early_data_status(ssl: *const ffi::SSL) -> c_int;
+    fn SSL_get_verify_callback(ssl: *const ffi::SSL)
+        -> Option<extern "C" fn(c_int, *mut ffi::X509_STORE_CTX) -> c_int>;
+}
+
+#[cfg(have_ossl111)]
+const SSL_EARLY_DATA_ACCEPTED: c_int = 2;
+
+// `SSL_get_negotiated_group` is a macro over `SSL_ctrl`.
+#[cfg(all(have_ossl300, not(have_ossl320)))]
+const SSL_CTRL_GET_NEGOTIATED_GROUP: c_int = 134;
+
+/// The maximum plaintext size of a TLS record.
+const MAX_RECORD_SIZE: usize = 16 * 1024;
+
+// Not exposed by `openssl-sys`.
+const SSL_R_UNSAFE_LEGACY_RENEGOTIATION_DISABLED: c_int = 338;
+const SSL_R_NO_RENEGOTIATION: c_int = 339;
 
 /// A stream managing a TLS session.
-pub struct TlsStream<S>(pub(crate) ssl::SslStream<S>);
+pub struct TlsStream<S>(pub(crate) ssl::SslStream<S>, ByteCounts);
+
+/// The application data bytes transferred over a stream.
+#[derive(Debug, Default)]
+struct ByteCounts {
+    read: u64,
+    written: u64,
+}
 
 impl<S: fmt::Debug> fmt::Debug for TlsStream<S> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -12,6 +53,10 @@ impl<S: fmt::Debug> fmt::Debug for TlsStream<S> {
 }
 
 impl<S> TlsStream<S> {
+    pub(crate) fn new(stream: ssl::SslStream<S>) -> Self {
+        TlsStream(stream, ByteCounts::default())
+    }
+
     /// Returns a shared reference to the inner stream.
     pub fn get_ref(&self) -> &S {
         self.0.get_ref()
@@ -21,20 +66,198 @@ impl<S> TlsStream<S> {
     pub fn get_mut(&mut self) -> &mut S {
         self.0.get_mut()
     }
-}
 
-impl<S: io::Read + io::Write> TlsStream<S> {
+    /// Returns the underlying OpenSSL connection, for queries this crate doesn't expose, such
+    /// as the negotiated group or the verified chain.
+    ///
+    /// Code relying on it is tied to the version of the `openssl` crate used by this crate.
+    pub fn ssl(&self) -> &ssl::SslRef {
+        self.0.ssl()
+    }
+
     /// Returns the number of bytes that can be read without resulting in any
     /// network calls.
     pub fn buffered_read_size(&self) -> crate::Result<usize> {
         Ok(self.0.ssl().pending())
     }
 
+    /// Returns the number of application data bytes read from the stream so far, including
+    /// early data.
+    pub fn bytes_read(&self) -> u64 {
+        self.1.read
+    }
+
+    /// Returns the number of application data bytes written to the stream so far, including
+    /// early data.
+    pub fn bytes_written(&self) -> u64 {
+        self.1.written
+    }
+
     /// Returns the peer's leaf certificate, if available.
+    ///
+    /// The certificate is read from the current session on every call. Once a renegotiation
+    /// completed, the certificate the peer presented in it is returned, unless the previous
+    /// session was resumed.
     pub fn peer_certificate(&self) -> crate::Result<Option<Certificate>> {
         Ok(self.0.ssl().peer_certificate().map(Certificate::from))
     }
 
+    /// Returns whether the current time lies within the validity period of the peer's leaf
+    /// certificate, or `None` if the peer sent no certificate.
+    ///
+    /// Useful with long-lived connections, whose certificate may expire after the handshake.
+    pub fn peer_certificate_valid_now(&self) -> crate::Result<Option<bool>> {
+        let cert = match self.0.ssl().peer_certificate() {
+            Some(cert) => cert,
+            None => return Ok(None),
+        };
+
+        let now = Asn1Time::days_from_now(0)?;
+        let started = cert.not_before().compare(&now)? != Ordering::Greater;
+        let expired = cert.not_after().compare(&now)? == Ordering::Less;
+
+        Ok(Some(started && !expired))
+    }
+
+    /// Returns the peer's certificate chain as built and verified by OpenSSL, from the leaf
+    /// certificate to the trust anchor, if available.
+    ///
+    /// Unlike the chain sent by the peer, it is ordered and includes the root certificate that
+    /// established trust. It may be incomplete if the verification failed, e.g. with
+    /// `danger_accept_invalid_certs`. Requires OpenSSL 1.1.0 or newer.
+    #[cfg(have_ossl110)]
+    pub fn verified_chain(&self) -> crate::Result<Option<Vec<Certificate>>> {
+        Ok(self
+            .0
+            .ssl()
+            .verified_chain()
+            .map(|chain| chain.iter().map(|cert| Certificate::from(cert.to_owned())).collect()))
+    }
+
+    /// Returns the fingerprint of the peer's leaf certificate, if available.
+    ///
+    /// See `Certificate::fingerprint`.
+    pub fn peer_certificate_fingerprint(&self, digest: MessageDigest) -> crate::Result<Option<Vec<u8>>> {
+        match self.0.ssl().peer_certificate() {
+            Some(cert) => Ok(Some(cert.digest(digest)?.to_vec())),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the DER-encoded OCSP response stapled by the server, if available.
+    ///
+    /// The server only staples a response if the connector requested one with
+    /// `TlsConnectorBuilder::set_status_request`.
+    pub fn ocsp_response(&self) -> crate::Result<Option<Vec<u8>>> {
+        Ok(self.0.ssl().ocsp_status().map(|r| r.to_vec()))
+    }
+
+    /// Returns the host name requested by the client with SNI, or `None` if it sent none.
+    ///
+    /// On the server side, this allows routing a connection to the handler of the requested
+    /// virtual host.
+    pub fn servername(&self) -> Option<String> {
+        self.0.ssl().servername(ssl::NameType::HOST_NAME).map(String::from)
+    }
+
+    /// Returns the name of the group negotiated for the key exchange, such as `x25519` or
+    /// `secp256r1`, or `None` before the handshake completed.
+    ///
+    /// Requires OpenSSL 3.0 or newer, and returns `None` with older versions. Before OpenSSL
+    /// 3.2, the name is the OpenSSL short name of the group, such as `prime256v1`, and groups
+    /// without one aren't reported.
+    pub fn negotiated_group(&self) -> Option<String> {
+        negotiated_group(self.0.ssl())
+    }
+
+    /// Returns `true` if this is the server side of the connection.
+    pub fn is_server(&self) -> bool {
+        self.0.ssl().is_server()
+    }
+
+    /// Returns `true` if this is the client side of the connection.
+    pub fn is_client(&self) -> bool {
+        !self.is_server()
+    }
+
+    /// Returns `true` if the connection resumed a previously negotiated session.
+    pub fn session_reused(&self) -> bool {
+        self.0.ssl().session_reused()
+    }
+
+    /// Returns the negotiated protocol, cipher suite and ALPN protocol, together with the
+    /// peer's certificate and whether the session was resumed.
+    pub fn connection_info(&self) -> crate::Result<ConnectionInfo> {
+        let ssl = self.0.ssl();
+
+        Ok(ConnectionInfo {
+            protocol: ssl.version_str().into(),
+            cipher: ssl.current_cipher().map(|c| c.name().into()),
+            alpn_protocol: ssl.selected_alpn_protocol().map(|p| p.to_vec()),
+            session_reused: ssl.session_reused(),
+            peer_certificate: self.peer_certificate()?,
+        })
+    }
+
+    /// Requests a certificate from the client of an established TLS 1.3 connection.
+    ///
+    /// The request is sent with the next write to the stream, and the certificate is
+    /// available from `peer_certificate` once the client's answer was read. It is verified
+    /// against the roots added with `TlsAcceptorBuilder::add_root_certificate`, failing the
+    /// read if invalid; a client may also decline to send one.
+    ///
+    /// Fails if the connection is not TLS 1.3, or if the client didn't enable post-handshake
+    /// authentication with `TlsConnectorBuilder::enable_post_handshake_auth`. Requires
+    /// OpenSSL 1.1.1 or newer.
+    #[cfg(have_ossl111)]
+    pub fn request_client_auth(&mut self) -> crate::Result<()> {
+        let ssl = self.0.ssl().as_ptr();
+
+        // `SslStream` only hands out a shared `SslRef`, so set the verify mode directly, keeping
+        // the verify callback of the acceptor.
+        unsafe {
+            let mode = ffi::SSL_get_verify_mode(ssl) | ffi::SSL_VERIFY_PEER;
+            ffi::SSL_set_verify(ssl, mode, SSL_get_verify_callback(ssl));
+        }
+
+        if unsafe { SSL_verify_client_post_handshake(ssl) } != 1 {
+            return Err(ErrorStack::get().into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the server accepted the early data of the connection.
+    ///
+    /// Only meaningful once the handshake completed. Early data rejected by the server was
+    /// discarded, and has to be written again. Requires OpenSSL 1.1.1 or newer.
+    #[cfg(have_ossl111)]
+    pub fn early_data_accepted(&self) -> bool {
+        unsafe { SSL_get_early_data_status(self.0.ssl().as_ptr()) == SSL_EARLY_DATA_ACCEPTED }
+    }
+
+    /// Returns the master secret of the session, or an empty vector before the handshake
+    /// completed.
+    ///
+    /// # Warning
+    ///
+    /// Anyone knowing the master secret can decrypt the whole connection. This is meant for
+    /// test harnesses verifying the key derivation, never use it in production. Requires
+    /// OpenSSL 1.1.0 or newer.
+    #[cfg(all(feature = "insecure-export-secrets", have_ossl110))]
+    #[cfg_attr(feature = "docs", doc(cfg(feature = "insecure-export-secrets")))]
+    pub fn master_key(&self) -> Vec<u8> {
+        match self.0.ssl().session() {
+            Some(session) => {
+                let mut key = vec![0; session.master_key_len()];
+                let len = session.master_key(&mut key);
+                key.truncate(len);
+                key
+            }
+            None => vec![],
+        }
+    }
+
     /// Returns the tls-server-end-point channel binding data as defined in [RFC 5929].
     ///
     /// [RFC 5929]: https://tools.ietf.org/html/rfc5929
@@ -68,31 +291,316 @@ impl<S: io::Read + io::Write> TlsStream<S> {
 
         Ok(Some(digest.to_vec()))
     }
+}
+
+/// Timeouts of the underlying TCP stream.
+///
+/// Other transports can be configured through [`get_ref`](TlsStream::get_ref) in the same way.
+impl TlsStream<TcpStream> {
+    /// Sets the read timeout of the underlying TCP stream, `None` blocking indefinitely.
+    ///
+    /// A read running into the timeout fails with an error of kind `WouldBlock` or
+    /// `TimedOut`, depending on the platform. The TLS session remains intact, so the read can
+    /// be retried.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use opentls::sync_io::TlsConnector;
+    /// use std::io::Read;
+    /// use std::net::TcpStream;
+    /// use std::time::Duration;
+    ///
+    /// let connector = TlsConnector::new().unwrap();
+    /// let stream = TcpStream::connect("google.com:443").unwrap();
+    /// let mut stream = connector.connect("google.com", stream).unwrap();
+    /// stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    ///
+    /// let mut buf = [0; 1024];
+    /// match stream.read(&mut buf) {
+    ///     Ok(n) => println!("read {} bytes", n),
+    ///     Err(e) => println!("no data within 5 seconds: {}", e),
+    /// }
+    /// ```
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+
+    /// Sets the write timeout of the underlying TCP stream, `None` blocking indefinitely.
+    ///
+    /// A write running into the timeout fails like a read does, see `set_read_timeout`.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_write_timeout(timeout)
+    }
+}
+
+impl<S: io::Read + io::Write> TlsStream<S> {
+    /// Reads like `read`, but leaves the bytes queued in the session.
+    #[cfg(any(feature = "io-tokio", feature = "io-async-std", feature = "io-futures"))]
+    pub(crate) fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.0.ssl_peek(buf) {
+                Ok(n) => return Ok(n),
+                Err(ref e) if e.code() == ssl::ErrorCode::ZERO_RETURN => return Ok(0),
+                Err(ref e) if e.code() == ssl::ErrorCode::SYSCALL && e.io_error().is_none() => return Ok(0),
+                Err(ref e) if e.code() == ssl::ErrorCode::WANT_READ && e.io_error().is_none() => {}
+                Err(e) => return Err(into_io_error(e)),
+            }
+        }
+    }
 
     /// Shuts down the TLS session.
     pub fn shutdown(&mut self) -> io::Result<()> {
         match self.0.shutdown() {
             Ok(_) => Ok(()),
             Err(ref e) if e.code() == ssl::ErrorCode::ZERO_RETURN => Ok(()),
-            Err(e) => Err(e
-                .into_io_error()
-                .unwrap_or_else(|e| io::Error::new(io::ErrorKind::Other, e))),
+            Err(e) => Err(into_io_error(e)),
+        }
+    }
+
+    /// Writes TLS 1.3 early data ("0-RTT") on a connection started with
+    /// `TlsConnector::connect_early`, before the handshake completes.
+    ///
+    /// Returns the number of bytes written. Fails if the session being resumed doesn't allow
+    /// early data, or once the handshake completed. Whether the server accepted the data is
+    /// only known after the handshake, see `early_data_accepted`. Requires OpenSSL 1.1.1 or
+    /// newer.
+    ///
+    /// # Warning
+    ///
+    /// Early data is not protected against replays: an attacker can record it and send it to
+    /// the server again, possibly many times. Only send requests which are safe to process
+    /// more than once, such as an idempotent `GET`, and never anything that changes state on
+    /// the server. Early data also lacks forward secrecy, it is only as safe as the ticket key
+    /// of the server.
+    #[cfg(have_ossl111)]
+    pub fn write_early_data(&mut self, buf: &[u8]) -> crate::Result<usize> {
+        let n = self.0.write_early_data(buf).map_err(into_io_error)?;
+        self.1.written += n as u64;
+        Ok(n)
+    }
+
+    /// Reads TLS 1.3 early data ("0-RTT") on a connection started with
+    /// `TlsAcceptor::accept_early`, before the handshake completes.
+    ///
+    /// Returns `0` once all early data was read, or if the client didn't send any. Call this
+    /// until it returns `0`, then complete the handshake with `handshake`. Data read
+    /// afterwards with `Read` was sent after the handshake. Requires OpenSSL 1.1.1 or newer.
+    ///
+    /// # Warning
+    ///
+    /// Early data is not protected against replays, see
+    /// `TlsAcceptorBuilder::set_max_early_data`. The application has to make sure acting on
+    /// it more than once is harmless, e.g. by only serving idempotent requests from it.
+    #[cfg(have_ossl111)]
+    pub fn read_early_data(&mut self, buf: &mut [u8]) -> crate::Result<usize> {
+        let n = self.0.read_early_data(buf).map_err(into_io_error)?;
+        self.1.read += n as u64;
+        Ok(n)
+    }
+
+    /// Completes the handshake of a connection started with `TlsConnector::setup`,
+    /// `TlsConnector::connect_early` or `TlsAcceptor::accept_early`.
+    ///
+    /// Returns immediately if the handshake already completed, which the first read or write
+    /// does as well. On a nonblocking stream, fails with an error whose I/O error is of kind
+    /// `WouldBlock` until the handshake can make progress; call it again once the stream is
+    /// ready.
+    pub fn handshake(&mut self) -> crate::Result<()> {
+        match self.0.do_handshake() {
+            Ok(()) => Ok(()),
+            Err(e) => Err(crate::Error::Ssl(e, self.0.ssl().verify_result())),
         }
     }
 }
 
+/// The name of the group negotiated for the key exchange, if the handshake completed.
+#[cfg(have_ossl320)]
+fn negotiated_group(ssl: &ssl::SslRef) -> Option<String> {
+    let name = unsafe { ffi::SSL_get0_group_name(ssl.as_ptr()) };
+
+    if name.is_null() {
+        return None;
+    }
+
+    Some(unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned())
+}
+
+#[cfg(all(have_ossl300, not(have_ossl320)))]
+fn negotiated_group(ssl: &ssl::SslRef) -> Option<String> {
+    let nid = unsafe { ffi::SSL_ctrl(ssl.as_ptr(), SSL_CTRL_GET_NEGOTIATED_GROUP, 0, ptr::null_mut()) };
+
+    match nid {
+        0 => None,
+        nid => Nid::from_raw(nid as c_int).short_name().ok().map(String::from),
+    }
+}
+
+#[cfg(not(have_ossl300))]
+fn negotiated_group(_: &ssl::SslRef) -> Option<String> {
+    None
+}
+
+/// Converts an OpenSSL error into an I/O error.
+///
+/// Errors of the underlying stream are returned as is, keeping their kind, so e.g. a
+/// connection reset can be told apart from a TLS protocol error. A refused renegotiation
+/// is wrapped as `Error::Renegotiation`.
+pub(crate) fn into_io_error(e: ssl::Error) -> io::Error {
+    if is_renegotiation_refused(&e) {
+        return io::Error::new(io::ErrorKind::InvalidData, crate::Error::Renegotiation(e));
+    }
+
+    let kind = match e.code() {
+        ssl::ErrorCode::SSL => io::ErrorKind::InvalidData,
+        // The peer closed the stream without a close_notify.
+        ssl::ErrorCode::SYSCALL => io::ErrorKind::UnexpectedEof,
+        _ => io::ErrorKind::Other,
+    };
+
+    e.into_io_error().unwrap_or_else(|e| io::Error::new(kind, e))
+}
+
+fn is_renegotiation_refused(e: &ssl::Error) -> bool {
+    e.ssl_error().is_some_and(|stack| {
+        stack.errors().iter().any(|e| {
+            matches!(
+                e.reason_code(),
+                SSL_R_UNSAFE_LEGACY_RENEGOTIATION_DISABLED | SSL_R_NO_RENEGOTIATION
+            )
+        })
+    })
+}
+
+// Renegotiation requests of the peer are handled by OpenSSL within `ssl_read` and
+// `ssl_write`, which retry until application data is transferred.
 impl<S: io::Read + io::Write> io::Read for TlsStream<S> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(buf)
+        loop {
+            match self.0.ssl_read(buf) {
+                Ok(n) => {
+                    self.1.read += n as u64;
+                    return Ok(n);
+                }
+                Err(ref e) if e.code() == ssl::ErrorCode::ZERO_RETURN => return Ok(0),
+                Err(ref e) if e.code() == ssl::ErrorCode::SYSCALL && e.io_error().is_none() => return Ok(0),
+                Err(ref e) if e.code() == ssl::ErrorCode::WANT_READ && e.io_error().is_none() => {}
+                Err(e) => return Err(into_io_error(e)),
+            }
+        }
     }
 }
 
 impl<S: io::Read + io::Write> io::Write for TlsStream<S> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.0.write(buf)
+        loop {
+            match self.0.ssl_write(buf) {
+                Ok(n) => {
+                    self.1.written += n as u64;
+                    return Ok(n);
+                }
+                Err(ref e) if e.code() == ssl::ErrorCode::WANT_READ && e.io_error().is_none() => {}
+                Err(e) => return Err(into_io_error(e)),
+            }
+        }
+    }
+
+    /// Coalesces the buffers into a single write of up to a full TLS record, instead of
+    /// sending a record per buffer.
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum::<usize>().min(MAX_RECORD_SIZE);
+
+        match bufs.iter().find(|buf| !buf.is_empty()) {
+            None => return Ok(0),
+            // The first buffer fills a record on its own.
+            Some(buf) if buf.len() >= len => return self.write(buf),
+            Some(_) => {}
+        }
+
+        let mut coalesced = Vec::with_capacity(len);
+
+        for buf in bufs {
+            let n = buf.len().min(len - coalesced.len());
+            coalesced.extend_from_slice(&buf[..n]);
+        }
+
+        self.write(&coalesced)
     }
 
     fn flush(&mut self) -> io::Result<()> {
         self.0.flush()
     }
 }
+
+/// A TLS stream which has been interrupted midway through the handshake process.
+///
+/// Returned from [`HandshakeError::into_mid_handshake`] when the underlying stream is
+/// nonblocking and not ready to perform I/O, on the client side as well as on the server
+/// side with `TlsAcceptor::accept`. Once the stream becomes readable or
+/// writable again, call [`handshake`](MidHandshakeTlsStream::handshake) to resume,
+/// repeating until it either completes or fails.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use opentls::sync_io::TlsConnector;
+/// use std::net::TcpStream;
+///
+/// let connector = TlsConnector::new().unwrap();
+///
+/// let stream = TcpStream::connect("google.com:443").unwrap();
+/// stream.set_nonblocking(true).unwrap();
+///
+/// let mut result = connector.connect("google.com", stream);
+///
+/// let stream = loop {
+///     match result {
+///         Ok(stream) => break stream,
+///         Err(e) => match e.into_mid_handshake() {
+///             Some(mid) => {
+///                 // wait here until the socket is readable or writable,
+///                 // e.g. with `poll` or `select`
+///                 result = mid.handshake();
+///             }
+///             None => panic!("handshake failed"),
+///         },
+///     }
+/// };
+/// ```
+pub struct MidHandshakeTlsStream<S>(pub(crate) ssl::MidHandshakeSslStream<S>);
+
+impl<S: fmt::Debug> fmt::Debug for MidHandshakeTlsStream<S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, fmt)
+    }
+}
+
+impl<S> MidHandshakeTlsStream<S> {
+    /// Returns a shared reference to the inner stream.
+    pub fn get_ref(&self) -> &S {
+        self.0.get_ref()
+    }
+
+    /// Returns a mutable reference to the inner stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        self.0.get_mut()
+    }
+}
+
+impl<S: io::Read + io::Write> MidHandshakeTlsStream<S> {
+    /// Restarts the handshake process.
+    ///
+    /// If the handshake completes successfully then the negotiated stream is returned. If
+    /// there is a problem, then an error is returned. Note that the error may not be fatal.
+    /// For example if the underlying stream is an asynchronous one then `HandshakeError::WouldBlock`
+    /// may just mean to wait for more I/O to happen later.
+    pub fn handshake(self) -> Result<TlsStream<S>, HandshakeError<S>> {
+        Ok(TlsStream::new(self.0.handshake()?))
+    }
+}
+
+impl<S> From<ssl::MidHandshakeSslStream<S>> for MidHandshakeTlsStream<S> {
+    fn from(inner: ssl::MidHandshakeSslStream<S>) -> Self {
+        Self(inner)
+    }
+}
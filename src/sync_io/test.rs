@@ -2,6 +2,7 @@ use hex;
 #[allow(unused_imports)]
 use std::io::{Read, Write};
 use std::net::{TcpListener, TcpStream};
+use std::path::Path;
 use std::thread;
 
 macro_rules! p {
@@ -16,12 +17,128 @@ macro_rules! p {
 // This nested mod is needed for ios testing with rust-test-ios
 mod tests {
     use crate::{
-        sync_io::{TlsAcceptor, TlsConnector},
-        Certificate, Identity, Protocol,
+        sync_io::{TlsAcceptor, TlsConnector, TlsConnectorBuilder, TlsStream},
+        Certificate, Error, HandshakeError, Identity, MessageDigest, Protocol, SecurityProfile, SessionStore,
+        VerifyError,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
     };
 
     use super::*;
 
+    /// The identity of `tests/identity.p12`, an expired certificate for `foobar.com`.
+    fn test_identity() -> Identity {
+        let buf = include_bytes!("../../tests/identity.p12");
+        p!(Identity::from_pkcs12(buf, "mypass"))
+    }
+
+    /// An acceptor with the identity of `tests/identity.p12`.
+    fn identity_acceptor() -> TlsAcceptor {
+        p!(TlsAcceptor::new(test_identity()))
+    }
+
+    /// The identity of `tests/ip.p12`, valid for `127.0.0.1`.
+    fn ip_identity() -> Identity {
+        let buf = include_bytes!("../../tests/ip.p12");
+        p!(Identity::from_pkcs12(buf, "mypass"))
+    }
+
+    /// An acceptor with the identity of `tests/expired.p12`, an expired certificate for
+    /// `127.0.0.1`.
+    fn expired_acceptor() -> TlsAcceptor {
+        let buf = include_bytes!("../../tests/expired.p12");
+        p!(TlsAcceptor::new(p!(Identity::from_pkcs12(buf, "mypass"))))
+    }
+
+    /// An acceptor with the identity of `tests/ip.p12`.
+    fn ip_acceptor() -> TlsAcceptor {
+        p!(TlsAcceptor::new(ip_identity()))
+    }
+
+    /// An acceptor with the identity of `tests/revoked.p12`, revoked by `tests/crl.pem`.
+    fn revoked_acceptor() -> TlsAcceptor {
+        let buf = include_bytes!("../../tests/revoked.p12");
+        p!(TlsAcceptor::new(p!(Identity::from_pkcs12(buf, "mypass"))))
+    }
+
+    /// A connector builder trusting only `tests/ip-cert.pem`, the certificate of `ip_identity`.
+    fn ip_connector() -> TlsConnectorBuilder {
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let mut builder = TlsConnector::builder();
+        builder.disable_built_in_roots(true).add_root_certificate(cert);
+        builder
+    }
+
+    /// Accepts `n` TCP connections on a free port, handing each socket to `f`.
+    fn listen<F>(n: usize, mut f: F) -> (u16, thread::JoinHandle<()>)
+    where
+        F: FnMut(TcpStream) + Send + 'static,
+    {
+        let listener = p!(TcpListener::bind("0.0.0.0:0"));
+        let port = p!(listener.local_addr()).port();
+
+        let j = thread::spawn(move || {
+            for _ in 0..n {
+                f(p!(listener.accept()).0);
+            }
+        });
+
+        (port, j)
+    }
+
+    /// Accepts a single TCP connection on a free port, handing the socket to `f`.
+    fn listen_once<F, T>(f: F) -> (u16, thread::JoinHandle<T>)
+    where
+        F: FnOnce(TcpStream) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let listener = p!(TcpListener::bind("0.0.0.0:0"));
+        let port = p!(listener.local_addr()).port();
+
+        let j = thread::spawn(move || f(p!(listener.accept()).0));
+
+        (port, j)
+    }
+
+    /// Accepts `n` connections with `acceptor` on a free port, handing each stream to `f`.
+    fn serve<F>(acceptor: TlsAcceptor, n: usize, mut f: F) -> (u16, thread::JoinHandle<()>)
+    where
+        F: FnMut(TlsStream<TcpStream>) + Send + 'static,
+    {
+        listen(n, move |socket| f(p!(acceptor.accept(socket))))
+    }
+
+    /// Accepts a single connection with `acceptor` on a free port, handing the stream to `f`.
+    fn serve_once<F, T>(acceptor: TlsAcceptor, f: F) -> (u16, thread::JoinHandle<T>)
+    where
+        F: FnOnce(TlsStream<TcpStream>) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        listen_once(move |socket| f(p!(acceptor.accept(socket))))
+    }
+
+    /// Accepts `n` connections with `acceptor` on a free port, writing `hello` and shutting
+    /// down each one completing the handshake. Failed handshakes are left to the client to
+    /// check.
+    fn serve_hello(acceptor: TlsAcceptor, n: usize) -> (u16, thread::JoinHandle<()>) {
+        listen(n, move |socket| {
+            if let Ok(mut socket) = acceptor.accept(socket) {
+                let _ = socket.write_all(b"hello");
+                let _ = socket.shutdown();
+            }
+        })
+    }
+
+    /// Connects to `port` as `foobar.com`, accepting the untrusted certificate of the test
+    /// identity.
+    fn connect_untrusted(port: u16) -> TlsStream<TcpStream> {
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        p!(connector.connect("foobar.com", socket))
+    }
+
     #[test]
     fn connect_google() {
         let builder = p!(TlsConnector::new());
@@ -246,13 +363,7 @@ mod tests {
             .min_protocol_version(Some(Protocol::Tlsv12))
             .build());
 
-        let listener = p!(TcpListener::bind("0.0.0.0:0"));
-        let port = p!(listener.local_addr()).port();
-
-        let j = thread::spawn(move || {
-            let socket = p!(listener.accept()).0;
-            assert!(builder.accept(socket).is_err());
-        });
+        let (port, j) = serve_hello(builder, 1);
 
         let root_ca = include_bytes!("../../tests/root-ca.der");
         let root_ca = Certificate::from_der(root_ca).unwrap();
@@ -267,6 +378,96 @@ mod tests {
         p!(j.join());
     }
 
+    #[test]
+    #[cfg(have_ossl111)]
+    fn tls13_only() {
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .min_protocol_version(Some(Protocol::Tlsv13))
+            .build());
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        let mut builder = TlsConnector::builder();
+        builder.danger_accept_invalid_certs(true);
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(builder.max_protocol_version(Some(Protocol::Tlsv12)).build());
+        assert!(connector.connect("foobar.com", socket).is_err());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(builder
+            .min_protocol_version(Some(Protocol::Tlsv13))
+            .max_protocol_version(Some(Protocol::Tlsv13))
+            .build());
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+        assert_eq!(p!(socket.connection_info()).protocol, "TLSv1.3");
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn security_profile_modern() {
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .security_profile(SecurityProfile::Modern)
+            .build());
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        let mut builder = TlsConnector::builder();
+        builder.danger_accept_invalid_certs(true);
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(builder.max_protocol_version(Some(Protocol::Tlsv12)).build());
+        assert!(connector.connect("foobar.com", socket).is_err());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(builder.max_protocol_version(None).build());
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(p!(socket.connection_info()).protocol, "TLSv1.3");
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl110)]
+    fn connect_tls10_only_server() {
+        // TLS 1.0 needs SHA-1, which OpenSSL 3 only allows at security level 0.
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .min_protocol_version(Some(Protocol::Tlsv10))
+            .max_protocol_version(Some(Protocol::Tlsv10))
+            .set_security_level(0)
+            .build());
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        let mut builder = TlsConnector::builder();
+        builder.danger_accept_invalid_certs(true).set_security_level(0);
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        assert!(p!(builder.build()).connect("foobar.com", socket).is_err());
+
+        let connector = p!(builder.min_protocol_version(Some(Protocol::Tlsv10)).build());
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
     #[test]
     fn server_untrusted() {
         let buf = include_bytes!("../../tests/identity.p12");
@@ -290,6 +491,2292 @@ mod tests {
         p!(j.join());
     }
 
+    #[test]
+    fn server_untrusted_verify_error() {
+        let (port, j) = serve_hello(identity_acceptor(), 1);
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let builder = p!(TlsConnector::new());
+        let res = builder.connect("foobar.com", socket);
+
+        let verify_result = res.as_ref().err().and_then(HandshakeError::verify_result);
+        assert!(verify_result.is_some());
+
+        match res {
+            Err(HandshakeError::Failure(e)) => {
+                assert!(e.is_verify_error());
+                assert_eq!(e.verify_result().map(|v| v.as_raw()), verify_result.map(|v| v.as_raw()));
+                assert!(!e.is_io());
+            }
+            Err(HandshakeError::WouldBlock(_)) => panic!("unexpected WouldBlock"),
+            Ok(_) => panic!("handshake succeeded with an untrusted certificate"),
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connector_builder_clone() {
+        let acceptor = identity_acceptor();
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        let base = TlsConnector::builder();
+        let mut lenient = base.clone();
+        lenient.danger_accept_invalid_certs(true);
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        p!(p!(lenient.build()).connect("foobar.com", socket));
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let strict = p!(base.build());
+        assert!(strict.connect("foobar.com", socket).is_err());
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn dtls_server() {
+        use crate::dtls::{DtlsAcceptor, DtlsConnector, UdpChannel};
+        use std::net::UdpSocket;
+
+        let identity = test_identity();
+        let acceptor = p!(DtlsAcceptor::new(identity));
+
+        let server = p!(UdpSocket::bind("127.0.0.1:0"));
+        let client = p!(UdpSocket::bind("127.0.0.1:0"));
+        p!(server.connect(p!(client.local_addr())));
+        p!(client.connect(p!(server.local_addr())));
+
+        let j = thread::spawn(move || {
+            let mut socket = p!(acceptor.accept(UdpChannel::new(server)));
+
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            assert_eq!(&buf, b"hello");
+
+            p!(socket.write_all(b"world"));
+        });
+
+        let connector = p!(DtlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let mut socket = p!(connector.connect("foobar.com", UdpChannel::new(client)));
+
+        p!(socket.write_all(b"hello"));
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"world");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn verify_chain() {
+        // The leaf of `revoked.p12` is only revoked by `crl.pem`, which isn't checked here.
+        let identity = p!(Identity::from_pkcs12(
+            include_bytes!("../../tests/revoked.p12"),
+            "mypass"
+        ));
+        let cert = Certificate(identity.cert);
+        let root_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+
+        assert!(p!(cert.verify_chain(&[], &[root_ca])));
+    }
+
+    #[test]
+    fn verify_chain_incomplete() {
+        let identity = p!(Identity::from_pkcs12(
+            include_bytes!("../../tests/revoked.p12"),
+            "mypass"
+        ));
+        let cert = Certificate(identity.cert);
+        let other = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+
+        assert!(!p!(cert.verify_chain(&[], &[])));
+        assert!(!p!(cert.verify_chain(&[], &[other])));
+    }
+
+    #[test]
+    fn connect_revoked() {
+        let (port, j) = serve_hello(revoked_acceptor(), 2);
+
+        let root_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        let mut builder = TlsConnector::builder();
+        builder.add_root_certificate(root_ca);
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        p!(p!(builder.build()).connect("foobar.com", socket));
+
+        p!(builder.add_crl(include_bytes!("../../tests/crl.pem")));
+        let socket = p!(TcpStream::connect(("localhost", port)));
+
+        match p!(builder.build()).connect("foobar.com", socket) {
+            Err(HandshakeError::Failure(e)) => assert!(e.is_verify_error()),
+            Err(HandshakeError::WouldBlock(_)) => panic!("unexpected WouldBlock"),
+            Ok(_) => panic!("handshake succeeded with a revoked certificate"),
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn add_crl_invalid() {
+        let mut builder = TlsConnector::builder();
+        assert!(builder.add_crl(b"not a crl").is_err());
+    }
+
+    #[test]
+    fn acceptor_from_pkcs12_file() {
+        let acceptor = p!(TlsAcceptor::from_pkcs12_file("tests/identity.p12", "mypass"));
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn acceptor_from_missing_pkcs12_file() {
+        match TlsAcceptor::from_pkcs12_file("tests/missing.p12", "mypass") {
+            Err(e) => assert!(e.is_io()),
+            Ok(_) => panic!("built an acceptor from a missing file"),
+        }
+    }
+
+    #[test]
+    fn identity_to_pkcs12() {
+        let buf = include_bytes!("../../tests/identity.p12");
+        let mut identity = p!(Identity::from_pkcs12(buf, "mypass"));
+
+        // A second, unrelated certificate to check the chain order is kept.
+        let other_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        identity.chain.push(other_ca.0);
+
+        let der = p!(identity.to_pkcs12("otherpass", "foobar.com"));
+        let exported = p!(Identity::from_pkcs12(&der, "otherpass"));
+
+        assert_eq!(p!(exported.cert.to_der()), p!(identity.cert.to_der()));
+        assert_eq!(
+            p!(exported.pkey.private_key_to_der()),
+            p!(identity.pkey.private_key_to_der())
+        );
+
+        let chain: Vec<_> = identity.chain.iter().map(|c| p!(c.to_der())).collect();
+        let exported_chain: Vec<_> = exported.chain.iter().map(|c| p!(c.to_der())).collect();
+        assert_eq!(exported_chain, chain);
+
+        assert!(Identity::from_pkcs12(&der, "mypass").is_err());
+    }
+
+    #[test]
+    fn identity_from_der() {
+        let buf = include_bytes!("../../tests/ip.p12");
+        let parsed = p!(Identity::from_pkcs12(buf, "mypass"));
+        let cert = p!(parsed.cert.to_der());
+        let key = p!(parsed.pkey.private_key_to_der());
+
+        let identity = p!(Identity::from_der(&cert, &key));
+        assert_eq!(p!(identity.cert.to_der()), cert);
+        assert!(identity.chain.is_empty());
+
+        assert!(Identity::from_der(&cert, &cert).is_err());
+        assert!(Identity::from_der(&key, &key).is_err());
+
+        let acceptor = p!(TlsAcceptor::new(identity));
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let root = p!(Certificate::from_der(&cert));
+        let connector = p!(TlsConnector::builder()
+            .disable_built_in_roots(true)
+            .add_root_certificate(root)
+            .build());
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn servername() {
+        let acceptor = identity_acceptor();
+
+        let mut expected = vec![Some("foobar.com"), None].into_iter();
+        let (port, j) = serve(acceptor, 2, move |socket| {
+            assert_eq!(socket.servername().as_deref(), expected.next().unwrap());
+            assert!(socket.is_server() && !socket.is_client());
+        });
+
+        let mut builder = TlsConnector::builder();
+        builder.danger_accept_invalid_certs(true);
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let socket = p!(p!(builder.build()).connect("foobar.com", socket));
+        assert!(socket.is_client() && !socket.is_server());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        p!(p!(builder.use_sni(false).build()).connect("foobar.com", socket));
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn sni_and_hostname_verification() {
+        use openssl::ssl::NameType;
+        use std::sync::{Arc, Mutex};
+
+        let names = Arc::new(Mutex::new(vec![]));
+        let buf = include_bytes!("../../tests/ip.p12");
+        let identity = p!(Identity::from_pkcs12(buf, "mypass"));
+        let acceptor = {
+            let names = names.clone();
+            p!(TlsAcceptor::builder(identity)
+                .with_openssl(move |acceptor| {
+                    let names = names.clone();
+                    acceptor.set_servername_callback(move |ssl, _| {
+                        let name = ssl.servername(NameType::HOST_NAME).map(String::from);
+                        names.lock().unwrap().push(name);
+                        Ok(())
+                    });
+                    Ok(())
+                })
+                .build())
+        };
+
+        let (port, j) = serve_hello(acceptor, 4);
+
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+
+        // The certificate is only valid for IP addresses, so verifying the domain fails.
+        for &(use_sni, verify) in &[(true, true), (true, false), (false, true), (false, false)] {
+            let connector = p!(TlsConnector::builder()
+                .add_root_certificate(cert.clone())
+                .use_sni(use_sni)
+                .danger_accept_invalid_hostnames(!verify)
+                .build());
+
+            let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+            match connector.connect("foobar.com", socket) {
+                Ok(mut socket) => {
+                    assert!(!verify);
+                    let mut buf = [0; 5];
+                    p!(socket.read_exact(&mut buf));
+                }
+                Err(e) => {
+                    assert!(verify);
+                    assert_eq!(e.verify_error(), Some(crate::VerifyError::HostnameMismatch));
+                }
+            }
+
+            let name = names.lock().unwrap().pop().unwrap();
+            assert_eq!(name.as_deref(), if use_sni { Some("foobar.com") } else { None });
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn write_vectored() {
+        use std::io::IoSlice;
+
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            // The header and body are sent in a single record.
+            let bufs = [IoSlice::new(b"head"), IoSlice::new(b""), IoSlice::new(b"body")];
+            assert_eq!(p!(socket.write_vectored(&bufs)), 8);
+
+            // Writes are capped at a full record.
+            let large = vec![1; 20000];
+            let bufs = [IoSlice::new(b"head"), IoSlice::new(&large)];
+            let n = p!(socket.write_vectored(&bufs));
+            assert_eq!(n, 16384);
+            p!(socket.write_all(&large[n - 4..]));
+            p!(socket.shutdown());
+        });
+
+        let mut socket = connect_untrusted(port);
+
+        let mut buf = [0; 8];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"headbody");
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(&buf[..4], b"head");
+        assert_eq!(buf[4..], vec![1; 20000][..]);
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn byte_counts() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            p!(socket.write_all(&[1; 20000]));
+            p!(socket.shutdown());
+
+            assert_eq!(socket.bytes_read(), 5);
+            assert_eq!(socket.bytes_written(), 20000);
+        });
+
+        let mut socket = connect_untrusted(port);
+        assert_eq!((socket.bytes_read(), socket.bytes_written()), (0, 0));
+
+        p!(socket.write_all(b"hello"));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+
+        assert_eq!(socket.bytes_read(), 20000);
+        assert_eq!(socket.bytes_written(), 5);
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl300)]
+    fn negotiated_group() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(b"hello"));
+
+            socket.negotiated_group()
+        });
+
+        let mut socket = connect_untrusted(port);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+
+        let group = socket.negotiated_group().expect("no negotiated group");
+        assert!(!group.is_empty());
+        assert_eq!(p!(j.join()), Some(group));
+    }
+
+    #[test]
+    fn read_ahead() {
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity).set_read_ahead(true).build());
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            let mut buf = vec![0; 64 * 1024];
+            p!(socket.read_exact(&mut buf));
+            assert!(buf.iter().all(|&b| b == 1));
+
+            p!(socket.write_all(&[2; 64 * 1024]));
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .set_read_ahead(true)
+            .build());
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        p!(socket.write_all(&[1; 64 * 1024]));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf.len(), 64 * 1024);
+        assert!(buf.iter().all(|&b| b == 2));
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn psk() {
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .set_psk_server_callback(|identity| match identity {
+                Some(b"client") => Some(b"0123456789abcdef".to_vec()),
+                _ => None,
+            })
+            .build());
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        // The server certificate isn't trusted, the handshake only succeeds with the PSK.
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(TlsConnector::builder()
+            .set_psk_client_callback(|_| Some((b"client".to_vec(), b"0123456789abcdef".to_vec())))
+            .build());
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"hello");
+        assert!(socket.peer_certificate().unwrap().is_none());
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl110h)]
+    fn hardening_toggles() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .disable_compression(true)
+            .disable_renegotiation(true)
+            .build());
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn client_cert_chain() {
+        use openssl::pkcs12::Pkcs12;
+
+        let identity = test_identity();
+        let client_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .add_root_certificate(client_ca.clone())
+            .request_client_auth()
+            .build());
+
+        let expected = p!(client_ca.to_der());
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            // The chain sent by the client, without its leaf certificate.
+            let chain = socket.ssl().peer_cert_chain().unwrap();
+            assert_eq!(chain.len(), 1);
+            assert_eq!(p!(chain[0].to_der()), expected);
+            p!(socket.write_all(b"hello"));
+        });
+
+        // The leaf certificate and key only, with the chain distributed separately.
+        let pkcs12 = p!(Pkcs12::from_der(include_bytes!("../../tests/revoked.p12")));
+        let parsed = p!(pkcs12.parse2("mypass"));
+        let cert = p!(parsed.cert.unwrap().to_der());
+        let key = p!(parsed.pkey.unwrap().private_key_to_der());
+        let client_identity = p!(Identity::from_der(&cert, &key));
+
+        let connector = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .identity(client_identity)
+            .add_client_cert_chain(vec![client_ca])
+            .build());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(connector.connect("foobar.com", socket));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn client_cert_chain_without_identity() {
+        let client_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+
+        match TlsConnector::builder().add_client_cert_chain(vec![client_ca]).build() {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput),
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+    }
+
+    #[test]
+    #[cfg(have_ossl110)]
+    fn client_ca_name() {
+        use foreign_types::ForeignTypeRef;
+        use openssl::{stack::StackRef, x509::X509Name};
+        use std::cmp::Ordering;
+
+        extern "C" {
+            fn SSL_get_client_CA_list(ssl: *const openssl_sys::SSL) -> *mut openssl_sys::stack_st_X509_NAME;
+        }
+
+        let identity = test_identity();
+        let client_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .add_root_certificate(client_ca.clone())
+            .add_client_ca_name(client_ca.clone())
+            .request_client_auth()
+            .build());
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let mut socket = connect_untrusted(port);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+
+        // The names the server advertised in its certificate request.
+        let names = unsafe { SSL_get_client_CA_list(socket.ssl().as_ptr()) };
+        assert!(!names.is_null());
+        let names = unsafe { StackRef::<X509Name>::from_ptr(names) };
+        assert_eq!(names.len(), 1);
+        assert_eq!(p!(names[0].try_cmp(client_ca.0.subject_name())), Ordering::Equal);
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn request_client_auth() {
+        let identity = test_identity();
+        let client_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .add_root_certificate(client_ca)
+            .request_client_auth()
+            .build());
+
+        let mut expected = vec![true, false].into_iter();
+        let (port, j) = serve(acceptor.clone(), 2, move |mut socket| {
+            assert_eq!(p!(socket.peer_certificate()).is_some(), expected.next().unwrap());
+            p!(socket.write_all(b"hello"));
+        });
+
+        // A certificate from an unknown issuer is rejected.
+        let (rejecting_port, rejecting) = serve_hello(acceptor, 1);
+
+        let client_identity = p!(Identity::from_pkcs12(
+            include_bytes!("../../tests/revoked.p12"),
+            "mypass"
+        ));
+        let other_identity = p!(Identity::from_pkcs12(
+            include_bytes!("../../tests/identity.p12"),
+            "mypass"
+        ));
+
+        let clients = vec![
+            (Some(client_identity), port, true),
+            (None, port, true),
+            (Some(other_identity), rejecting_port, false),
+        ];
+
+        for (identity, port, accepted) in clients {
+            let mut builder = TlsConnector::builder();
+            builder.danger_accept_invalid_certs(true);
+
+            if let Some(identity) = identity {
+                builder.identity(identity);
+            }
+
+            let connector = p!(builder.build());
+            let socket = p!(TcpStream::connect(("localhost", port)));
+
+            // With TLS 1.3 the client only learns about the rejection on its first read.
+            let mut buf = [0; 5];
+            let res = connector
+                .connect("foobar.com", socket)
+                .map_err(|_| ())
+                .and_then(|mut s| s.read_exact(&mut buf).map_err(|_| ()));
+
+            assert_eq!(res.is_ok(), accepted);
+        }
+
+        p!(j.join());
+        p!(rejecting.join());
+    }
+
+    #[test]
+    fn connect_ip_address() {
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let connector = p!(TlsConnector::builder()
+            .disable_built_in_roots(true)
+            .add_root_certificate(cert)
+            .build());
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        // The certificate has no entry for any other address.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        assert!(connector.connect("127.0.0.2", socket).is_err());
+
+        p!(j.join());
+    }
+
+    extern "C" {
+        fn SSL_renegotiate(ssl: *mut openssl_sys::SSL) -> std::os::raw::c_int;
+    }
+
+    fn renegotiate<S>(socket: &TlsStream<S>) {
+        use foreign_types::ForeignTypeRef;
+
+        assert_eq!(unsafe { SSL_renegotiate(socket.ssl().as_ptr()) }, 1);
+    }
+
+    #[test]
+    fn renegotiation() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            renegotiate(&socket);
+            p!(socket.write_all(b"hello"));
+
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            assert_eq!(&buf, b"world");
+            p!(socket.write_all(b"again"));
+        });
+
+        let mut socket = connect_untrusted(port);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+        p!(socket.write_all(b"world"));
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"again");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn peer_certificate_after_renegotiation() {
+        use foreign_types::ForeignTypeRef;
+        use openssl::{
+            pkcs12::Pkcs12,
+            ssl::{SslOptions, SslRef},
+        };
+
+        let identity = test_identity();
+        // A resumed session would keep the previous certificate.
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .with_openssl(|acceptor| {
+                acceptor.set_options(SslOptions::NO_SESSION_RESUMPTION_ON_RENEGOTIATION);
+                Ok(())
+            })
+            .build());
+
+        // The certificate the server rotates to.
+        let pkcs12 = p!(Pkcs12::from_der(include_bytes!("../../tests/ip.p12")));
+        let parsed = p!(pkcs12.parse2("mypass"));
+        let (cert, key) = (parsed.cert.unwrap(), parsed.pkey.unwrap());
+        let expected = Certificate::from(cert.clone());
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            // `SslStream` hands out no mutable reference to the connection.
+            let ssl = unsafe { SslRef::from_ptr_mut(socket.ssl().as_ptr()) };
+            p!(ssl.set_certificate(&cert));
+            p!(ssl.set_private_key(&key));
+            renegotiate(&socket);
+            p!(socket.write_all(b"hello"));
+
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            assert_eq!(&buf, b"world");
+            p!(socket.write_all(b"again"));
+        });
+
+        let mut socket = connect_untrusted(port);
+        let before = p!(socket.peer_certificate()).unwrap();
+        assert_ne!(before, expected);
+
+        // The renegotiation completes while the data is exchanged.
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+        p!(socket.write_all(b"world"));
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"again");
+        assert_eq!(p!(socket.peer_certificate()), Some(expected));
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl110h)]
+    fn renegotiation_refused() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            renegotiate(&socket);
+            p!(socket.write_all(b"hello"));
+
+            let mut buf = [0; 5];
+            let err = Error::Io(socket.read_exact(&mut buf).unwrap_err());
+            assert!(err.is_renegotiation_error());
+        });
+
+        let connector = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .disable_renegotiation(true)
+            .build());
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+        let _ = socket.write_all(b"world");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connection_info() {
+        let (port, j) = serve_once(identity_acceptor(), move |socket| {
+            let info = p!(socket.connection_info());
+            assert!(info.peer_certificate.is_none());
+            info
+        });
+
+        let socket = connect_untrusted(port);
+
+        let info = p!(socket.connection_info());
+        assert_eq!(info.protocol, "TLSv1.2");
+        assert!(info.cipher.is_some());
+        assert_eq!(info.alpn_protocol, None);
+        assert!(!info.session_reused);
+
+        let cert = info.peer_certificate.unwrap();
+        assert_eq!(p!(cert.to_der()), include_bytes!("../../tests/cert.der").to_vec());
+
+        let server_info = p!(j.join());
+        assert_eq!(server_info.cipher, info.cipher);
+    }
+
+    #[test]
+    fn raw_ssl() {
+        let (port, j) = serve_once(identity_acceptor(), move |socket| {
+            let servername = socket.ssl().servername(openssl::ssl::NameType::HOST_NAME);
+            assert_eq!(servername, Some("foobar.com"));
+        });
+
+        let socket = connect_untrusted(port);
+
+        assert!(!socket.ssl().is_server());
+        assert_eq!(socket.ssl().version_str(), "TLSv1.2");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn set_ca_file() {
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let connector = p!(p!(TlsConnector::builder()
+            .disable_built_in_roots(true)
+            .set_ca_file(Path::new("tests/ip-cert.pem")))
+        .build());
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn set_missing_ca_file() {
+        match TlsConnector::builder().set_ca_file(Path::new("tests/missing.pem")) {
+            Err(e) => assert!(e.is_io()),
+            Ok(_) => panic!("set a missing CA file"),
+        }
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn max_fragment_length() {
+        use crate::MaxFragmentLength;
+        use foreign_types::ForeignTypeRef;
+
+        extern "C" {
+            fn SSL_SESSION_get_max_fragment_length(session: *const openssl_sys::SSL_SESSION) -> u8;
+        }
+
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(&[1; 2048]));
+        });
+
+        let connector = p!(p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .set_max_fragment_length(MaxFragmentLength::Bytes512))
+        .build());
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let session = socket.ssl().session().unwrap();
+        assert_eq!(unsafe { SSL_SESSION_get_max_fragment_length(session.as_ptr()) }, 1);
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, vec![1; 2048]);
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl110)]
+    fn verified_chain() {
+        let buf = include_bytes!("../../tests/revoked.p12");
+        let identity = p!(Identity::from_pkcs12(buf, "mypass"));
+        let leaf = p!(identity.cert.to_der());
+        let acceptor = p!(TlsAcceptor::new(identity));
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            assert!(p!(socket.verified_chain()).is_none());
+            p!(socket.write_all(b"hello"));
+        });
+
+        let root_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        let connector = p!(TlsConnector::builder()
+            .disable_built_in_roots(true)
+            .add_root_certificate(root_ca.clone())
+            .build());
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let chain = p!(socket.verified_chain()).unwrap();
+        let chain = chain.iter().map(|cert| p!(cert.to_der())).collect::<Vec<_>>();
+        assert_eq!(chain, vec![leaf, p!(root_ca.to_der())]);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        p!(j.join());
+    }
+
+    #[test]
+    fn read_timeout() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            // Stall until the client timed out.
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            p!(socket.write_all(b"hello"));
+        });
+
+        let mut socket = connect_untrusted(port);
+        p!(socket.set_read_timeout(Some(std::time::Duration::from_millis(100))));
+
+        let mut buf = [0; 5];
+        let err = socket.read(&mut buf).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        ));
+
+        // The session survives the timeout.
+        p!(socket.write_all(b"world"));
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn error_debug() {
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_hello(acceptor, 1);
+
+        let connector = p!(TlsConnector::builder().disable_built_in_roots(true).build());
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+
+        let debug = match connector.connect("127.0.0.1", socket) {
+            Err(HandshakeError::Failure(e)) => format!("{:?}", e),
+            Err(HandshakeError::WouldBlock(_)) => panic!("unexpected WouldBlock"),
+            Ok(_) => panic!("handshake succeeded with an untrusted certificate"),
+        };
+
+        assert!(debug.starts_with("Ssl { code: SSL, errors: Some([OpenSslError { code: "));
+        assert!(debug.contains("library: \"SSL routines\""));
+        assert!(debug.contains("reason: \"certificate verify failed\""));
+        assert!(debug.contains("self-signed certificate"));
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn verify_error_from_result() {
+        use openssl::x509::X509VerifyResult;
+
+        let cases = [
+            (openssl_sys::X509_V_ERR_CERT_HAS_EXPIRED, VerifyError::Expired),
+            (
+                openssl_sys::X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT,
+                VerifyError::SelfSigned,
+            ),
+            (
+                openssl_sys::X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY,
+                VerifyError::UnknownCa,
+            ),
+            (
+                openssl_sys::X509_V_ERR_IP_ADDRESS_MISMATCH,
+                VerifyError::HostnameMismatch,
+            ),
+            (openssl_sys::X509_V_ERR_CERT_REVOKED, VerifyError::Revoked),
+            (openssl_sys::X509_V_ERR_CERT_CHAIN_TOO_LONG, VerifyError::Other(22)),
+        ];
+
+        for &(code, expected) in &cases {
+            let result = unsafe { X509VerifyResult::from_raw(code) };
+            assert_eq!(VerifyError::from(result), expected);
+        }
+    }
+
+    #[test]
+    fn verify_error_revoked() {
+        let (port, j) = serve_hello(revoked_acceptor(), 1);
+
+        let root_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        let mut builder = TlsConnector::builder();
+        builder.add_root_certificate(root_ca);
+        p!(builder.add_crl(include_bytes!("../../tests/crl.pem")));
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        match p!(builder.build()).connect("foobar.com", socket) {
+            Err(e) => assert_eq!(e.verify_error(), Some(VerifyError::Revoked)),
+            Ok(_) => panic!("handshake succeeded with a revoked certificate"),
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn verify_callback_pinning() {
+        let buf = include_bytes!("../../tests/ip.p12");
+        let identity = p!(Identity::from_pkcs12(buf, "mypass"));
+        let pinned = p!(identity.cert.digest(MessageDigest::sha256())).to_vec();
+        let acceptor = p!(TlsAcceptor::new(identity));
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        let pinned_connector = |pinned: Vec<u8>| {
+            let mut builder = TlsConnector::builder();
+            builder.disable_built_in_roots(true).set_verify_callback(move |_, ctx| {
+                // The certificate is self-signed, so only its pin establishes trust.
+                match ctx.current_cert().map(|cert| cert.digest(MessageDigest::sha256())) {
+                    Some(Ok(digest)) => *digest == *pinned,
+                    _ => false,
+                }
+            });
+            p!(builder.build())
+        };
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(pinned_connector(pinned).connect("127.0.0.1", socket));
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        assert!(pinned_connector(vec![0; 32]).connect("127.0.0.1", socket).is_err());
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connect_host() {
+        let (port, j) = serve_hello(ip_acceptor(), 2);
+        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let connector = p!(TlsConnector::builder().add_root_certificate(cert).build());
+        let mut buf = [0; 5];
+
+        let socket = p!(TcpStream::connect(addr));
+        let mut socket = p!(connector.connect(addr, socket));
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        let socket = p!(TcpStream::connect(addr));
+        let mut socket = p!(connector.connect(addr.ip().to_string(), socket));
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn handshake_error_source() {
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_hello(acceptor, 1);
+
+        let connector = p!(TlsConnector::builder().disable_built_in_roots(true).build());
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let err: Box<dyn std::error::Error> = match connector.connect("127.0.0.1", socket) {
+            Ok(_) => panic!("handshake succeeded with an untrusted certificate"),
+            Err(e) => e.into(),
+        };
+
+        assert!(err.to_string().starts_with("TLS handshake failed: "));
+        let source = err.source().and_then(|e| e.downcast_ref::<Error>());
+        assert_eq!(source.and_then(Error::verify_error), Some(VerifyError::SelfSigned));
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connect_no_hostname_check() {
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_hello(acceptor, 3);
+
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let connector = p!(TlsConnector::builder()
+            .disable_built_in_roots(true)
+            .add_root_certificate(cert)
+            .build());
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect_no_hostname_check("10.0.0.1", socket));
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        // The relaxation doesn't leak to other connections.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        assert!(connector.connect("10.0.0.1", socket).is_err());
+
+        // The chain is still validated.
+        let connector = p!(TlsConnector::builder().disable_built_in_roots(true).build());
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        match connector.connect_no_hostname_check("10.0.0.1", socket) {
+            Err(e) => assert_eq!(e.verify_error(), Some(VerifyError::SelfSigned)),
+            Ok(_) => panic!("handshake succeeded with an untrusted certificate"),
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connect_no_hostname_check_session() {
+        let (port, j) = serve_hello(ip_acceptor(), 2);
+        let connector = p!(ip_connector().enable_session_cache(true).build());
+
+        // Reading to the end receives the session tickets of TLS 1.3.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect_no_hostname_check("10.0.0.1", socket));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"hello");
+
+        // The relaxed session isn't resumed, so the hostname is still checked.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        assert!(connector.connect("10.0.0.1", socket).is_err());
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn block_padding() {
+        // Counts the bytes written to the socket, records included.
+        #[derive(Debug)]
+        struct Counting {
+            inner: TcpStream,
+            written: usize,
+        }
+
+        impl Read for Counting {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+
+        impl Write for Counting {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let n = self.inner.write(buf)?;
+                self.written += n;
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.inner.flush()
+            }
+        }
+
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .min_protocol_version(Some(Protocol::Tlsv13))
+            .build());
+
+        let (port, j) = serve(acceptor, 2, |mut socket| {
+            let mut buf = [0; 1];
+            p!(socket.read_exact(&mut buf));
+            p!(socket.write_all(b"hello"));
+        });
+
+        let mut builder = TlsConnector::builder();
+        builder
+            .danger_accept_invalid_certs(true)
+            .min_protocol_version(Some(Protocol::Tlsv13))
+            .max_protocol_version(Some(Protocol::Tlsv13));
+
+        let mut sizes = vec![];
+        for &padding in &[0, 1024] {
+            let connector = p!(builder.set_block_padding(padding).build());
+            let socket = Counting {
+                inner: p!(TcpStream::connect(("localhost", port))),
+                written: 0,
+            };
+            let mut socket = p!(connector.connect("foobar.com", socket));
+
+            let before = socket.get_ref().written;
+            p!(socket.write_all(b"x"));
+            sizes.push(socket.get_ref().written - before);
+
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+        }
+
+        // The record header and tag come on top of the padded content.
+        assert!(sizes[0] < 1024);
+        assert!(sizes[1] > 1024);
+
+        p!(j.join());
+
+        assert!(builder.set_block_padding(20000).build().is_err());
+    }
+
+    #[test]
+    fn pkcs12_wrong_password() {
+        let buf = include_bytes!("../../tests/identity.p12");
+        let err = Identity::from_pkcs12(buf, "wrong").unwrap_err();
+        assert!(err.is_wrong_password());
+
+        let err = Identity::from_pkcs12(&buf[..buf.len() / 2], "mypass").unwrap_err();
+        assert!(!err.is_wrong_password());
+    }
+
+    #[test]
+    fn multiple_identities() {
+        use openssl::{
+            pkey::Id,
+            ssl::{SslConnector, SslMethod, SslVerifyMode},
+        };
+
+        let rsa = p!(Identity::from_pkcs12(
+            include_bytes!("../../tests/identity.p12"),
+            "mypass"
+        ));
+        let ecdsa = p!(Identity::from_pkcs12(include_bytes!("../../tests/ecdsa.p12"), "mypass"));
+        let acceptor = p!(TlsAcceptor::builder(rsa)
+            .max_protocol_version(Some(Protocol::Tlsv12))
+            .add_identity(ecdsa)
+            .build());
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        for &(sigalgs, key) in &[("ECDSA+SHA256", Id::EC), ("RSA+SHA256", Id::RSA)] {
+            let mut builder = p!(SslConnector::builder(SslMethod::tls()));
+            builder.set_verify(SslVerifyMode::NONE);
+            p!(builder.set_sigalgs_list(sigalgs));
+            let connector = TlsConnector::from_openssl(builder.build(), true, true, true);
+
+            let socket = p!(TcpStream::connect(("localhost", port)));
+            let mut socket = p!(connector.connect("foobar.com", socket));
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+
+            let cert = p!(socket.peer_certificate()).unwrap();
+            assert_eq!(p!(cert.0.public_key()).id(), key);
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connector_from_openssl() {
+        use openssl::ssl::{SslConnector, SslMethod};
+
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let mut builder = p!(SslConnector::builder(SslMethod::tls()));
+        p!(builder.cert_store_mut().add_cert(cert.0));
+        let connector = TlsConnector::from_openssl(builder.build(), true, false, false);
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        // The hostname is still verified.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        assert!(connector.connect("10.0.0.1", socket).is_err());
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(feature = "native-certs")]
+    fn native_certs() {
+        let mut builder = TlsConnector::builder();
+        p!(builder.disable_built_in_roots(true).with_native_certs());
+        assert!(!builder.root_certificates.is_empty());
+        p!(builder.build());
+    }
+
+    #[test]
+    fn root_certificate_dir() {
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let dir = std::env::temp_dir().join(format!("opentls-root-dir-{}", std::process::id()));
+        p!(std::fs::create_dir_all(&dir));
+        p!(std::fs::write(
+            dir.join("ip.pem"),
+            include_bytes!("../../tests/ip-cert.pem")
+        ));
+        p!(std::fs::write(dir.join("README"), b"not a certificate"));
+
+        let connector = p!(TlsConnector::builder()
+            .disable_built_in_roots(true)
+            .load_android_system_certs()
+            .add_root_certificate_dir(&dir)
+            .add_root_certificate_dir(Path::new("/nonexistent"))
+            .build());
+        p!(std::fs::remove_dir_all(&dir));
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn system_cert_env_vars_disabled() {
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        p!(TlsConnector::builder()
+            .use_system_cert_env_vars(false)
+            .add_root_certificate(cert)
+            .build());
+    }
+
+    #[test]
+    fn openssl_version() {
+        let (version, number) = crate::openssl_version();
+        assert!(version.contains("SSL"));
+        // OpenSSL 1.0.1 or newer.
+        assert!(number >= 0x1000_1000);
+    }
+
+    #[test]
+    fn rng_seeded() {
+        p!(crate::ensure_rng_seeded());
+        crate::seed_rng(&[0x5a; 48]);
+        p!(crate::ensure_rng_seeded());
+    }
+
+    #[test]
+    fn protocol_from_str() {
+        let parse = |s: &str| s.parse::<Protocol>().ok().map(|p| p.to_string());
+
+        assert_eq!(parse("1.2").as_deref(), Some("TLSv1.2"));
+        assert_eq!(parse("TLSv1.3").as_deref(), Some("TLSv1.3"));
+        assert_eq!(parse("tls 1.1").as_deref(), Some("TLSv1.1"));
+        assert_eq!(parse("TLS1").as_deref(), Some("TLSv1"));
+        assert_eq!(parse("1.0").as_deref(), Some("TLSv1"));
+        assert_eq!(parse("SSLv3").as_deref(), Some("SSLv3"));
+        assert_eq!(parse("TLSv1.4"), None);
+        assert_eq!(parse("ssl1.2"), None);
+        assert_eq!(parse(""), None);
+
+        for p in &[
+            Protocol::Sslv3,
+            Protocol::Tlsv10,
+            Protocol::Tlsv11,
+            Protocol::Tlsv12,
+            Protocol::Tlsv13,
+        ] {
+            assert_eq!(parse(&p.to_string()), Some(p.to_string()));
+        }
+    }
+
+    #[test]
+    fn duplicate_root_certificate() {
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        p!(TlsConnector::builder()
+            .add_root_certificate(cert.clone())
+            .add_root_certificate(cert)
+            .build());
+    }
+
+    #[test]
+    fn duplicate_root_certificate_acceptor_dtls() {
+        use crate::dtls::DtlsConnector;
+
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let identity = p!(Identity::from_pkcs12(include_bytes!("../../tests/ip.p12"), "mypass"));
+        p!(TlsAcceptor::builder(identity)
+            .add_root_certificate(cert.clone())
+            .add_root_certificate(cert.clone())
+            .build());
+        p!(DtlsConnector::builder()
+            .add_root_certificate(cert.clone())
+            .add_root_certificate(cert)
+            .build());
+    }
+
+    #[test]
+    fn connect_timeout() {
+        // A peer never answering the handshake, the connection waits in the backlog.
+        let silent = p!(TcpListener::bind("127.0.0.1:0"));
+        let (port, j) = serve_hello(identity_acceptor(), 1);
+
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+
+        let socket = p!(TcpStream::connect(p!(silent.local_addr())));
+        let err = connector
+            .connect_timeout("foobar.com", socket, std::time::Duration::from_millis(200))
+            .unwrap_err();
+        match err {
+            crate::Error::Io(ref e) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(connector.connect_timeout("foobar.com", socket, std::time::Duration::from_secs(10)));
+        assert_eq!(p!(socket.get_ref().read_timeout()), None);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(all(feature = "insecure-export-secrets", have_ossl110))]
+    fn master_key() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(b"hello"));
+
+            socket.master_key()
+        });
+
+        let mut socket = connect_untrusted(port);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+
+        let key = socket.master_key();
+        assert!(!key.is_empty());
+        assert_eq!(p!(j.join()), key);
+    }
+
+    #[test]
+    fn certificate_name_entries() {
+        let cert = p!(Certificate::from_der(include_bytes!("../../tests/cert.der")));
+        let entry = |key: &str, value: &str| (key.to_string(), value.to_string());
+
+        let issuer = vec![
+            entry("C", "AU"),
+            entry("ST", "Some-State"),
+            entry("O", "Internet Widgits Pty Ltd"),
+        ];
+        let mut subject = issuer.clone();
+        subject.push(entry("CN", "foobar.com"));
+
+        assert_eq!(p!(cert.subject_entries()), subject);
+        assert_eq!(p!(cert.issuer_entries()), issuer);
+    }
+
+    #[test]
+    fn certificate_eq_hash() {
+        use std::collections::HashSet;
+
+        let pem = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let der = p!(Certificate::from_der(&p!(pem.to_der())));
+        let other = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        assert_eq!(pem, der);
+        assert_ne!(pem, other);
+
+        let set = vec![pem, der, other].into_iter().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn add_root_certificates() {
+        let certs = vec![
+            p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem"))),
+            p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem"))),
+        ];
+
+        let mut builder = TlsConnector::builder();
+        builder.add_root_certificates(certs);
+        assert_eq!(builder.root_certificates.len(), 2);
+        p!(builder.build());
+    }
+
+    #[test]
+    fn with_method() {
+        use openssl::ssl::SslMethod;
+
+        let buf = include_bytes!("../../tests/ip.p12");
+        let identity = p!(Identity::from_pkcs12(buf, "mypass"));
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .with_method(SslMethod::tls_server())
+            .build());
+
+        let (port, j) = serve_hello(acceptor, 1);
+
+        let cert = p!(Certificate::from_pem(include_bytes!("../../tests/ip-cert.pem")));
+        let connector = p!(TlsConnector::builder()
+            .with_method(SslMethod::tls_client())
+            .add_root_certificate(cert)
+            .build());
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+
+        // A server-only method can't start a handshake.
+        let listener = p!(TcpListener::bind("127.0.0.1:0"));
+        let socket = p!(TcpStream::connect(p!(listener.local_addr())));
+        let connector = p!(TlsConnector::builder().with_method(SslMethod::tls_server()).build());
+        assert!(connector.connect("127.0.0.1", socket).is_err());
+    }
+
+    #[test]
+    fn peer_certificate_valid_now() {
+        let (port, j) = serve_once(ip_acceptor(), |mut socket| {
+            // The client sent no certificate.
+            assert_eq!(p!(socket.peer_certificate_valid_now()), None);
+            p!(socket.write_all(b"hello"));
+        });
+        let (expired_port, expired) = serve_hello(expired_acceptor(), 1);
+
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(p!(socket.peer_certificate_valid_now()), Some(true));
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", expired_port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(p!(socket.peer_certificate_valid_now()), Some(false));
+
+        p!(j.join());
+        p!(expired.join());
+    }
+
+    #[test]
+    fn accept_invalid_certs_except_expiry() {
+        let (port, j) = serve_hello(ip_acceptor(), 2);
+        let (expired_port, expired) = serve_hello(expired_acceptor(), 1);
+
+        let connector = p!(TlsConnector::builder()
+            .disable_built_in_roots(true)
+            .accept_invalid_certs_except_expiry()
+            .build());
+
+        // The self-signed certificate is accepted.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        // The hostname is still verified.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        assert!(connector.connect("10.0.0.1", socket).is_err());
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", expired_port)));
+        let res = connector.connect("127.0.0.1", socket);
+        let verify_error = res.as_ref().err().and_then(HandshakeError::verify_error);
+        assert_eq!(verify_error, Some(VerifyError::Expired));
+
+        p!(j.join());
+        p!(expired.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn post_handshake_auth() {
+        use openssl::ssl::{SslAcceptor, SslMethod, SslVerifyMode};
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        // `TlsAcceptor` doesn't negotiate TLS 1.3, so build a server that does.
+        let identity = test_identity();
+        let mut builder = p!(SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()));
+        p!(builder.set_private_key(&identity.pkey));
+        p!(builder.set_certificate(&identity.cert));
+        let client_ca = p!(Certificate::from_pem(include_bytes!("../../tests/crl-ca.pem")));
+        p!(builder.cert_store_mut().add_cert(client_ca.0));
+
+        // The verify callback of the acceptor still runs for the requested certificate.
+        let verified = Arc::new(AtomicUsize::new(0));
+        let counter = verified.clone();
+        builder.set_verify_callback(SslVerifyMode::NONE, move |ok, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            ok
+        });
+        let acceptor = TlsAcceptor(builder.build());
+
+        let (port, j) = serve_once(acceptor.clone(), move |mut socket| {
+            assert!(p!(socket.peer_certificate()).is_none());
+
+            p!(socket.request_client_auth());
+            p!(socket.write_all(b"hello"));
+
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            assert_eq!(&buf, b"world");
+            assert!(p!(socket.peer_certificate()).is_some());
+            assert!(verified.load(Ordering::SeqCst) > 0);
+        });
+
+        // Without post-handshake authentication on the client, the request fails.
+        let (unsupported_port, unsupported) = serve_once(acceptor, |mut socket| {
+            assert!(socket.request_client_auth().is_err());
+        });
+
+        let client_identity = p!(Identity::from_pkcs12(
+            include_bytes!("../../tests/revoked.p12"),
+            "mypass"
+        ));
+        let connector = p!(TlsConnector::builder()
+            .identity(client_identity)
+            .danger_accept_invalid_certs(true)
+            .enable_post_handshake_auth()
+            .build());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(connector.connect("foobar.com", socket));
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+        p!(socket.write_all(b"world"));
+
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let socket = p!(TcpStream::connect(("localhost", unsupported_port)));
+        let _socket = p!(connector.connect("foobar.com", socket));
+
+        p!(j.join());
+        p!(unsupported.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn write_early_data() {
+        use openssl::ssl::{Ssl, SslAcceptor, SslMethod, SslStream};
+
+        // `TlsAcceptor` doesn't negotiate TLS 1.3, so build a server that does.
+        let identity = ip_identity();
+        let mut builder = p!(SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()));
+        p!(builder.set_private_key(&identity.pkey));
+        p!(builder.set_certificate(&identity.cert));
+        p!(builder.set_max_early_data(1024));
+        let acceptor = builder.build();
+
+        let (port, j) = listen_once({
+            let acceptor = acceptor.clone();
+            move |socket| {
+                let mut socket = p!(acceptor.accept(socket));
+                p!(socket.write_all(b"hello"));
+                // An unclean shutdown would remove the session from the server cache.
+                p!(socket.shutdown());
+            }
+        });
+
+        // Sessions are resumed by host, the second server shares the ticket keys of the first.
+        let (early_port, early) = listen_once(move |socket| {
+            let mut socket = p!(SslStream::new(p!(Ssl::new(acceptor.context())), socket));
+
+            let mut early = vec![];
+            let mut buf = [0; 1024];
+
+            loop {
+                match p!(socket.read_early_data(&mut buf)) {
+                    0 => break,
+                    len => early.extend_from_slice(&buf[..len]),
+                }
+            }
+
+            p!(socket.accept());
+            assert_eq!(early, b"early");
+
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            assert_eq!(&buf, b"world");
+        });
+
+        let connector = p!(ip_connector().enable_session_cache(true).build());
+
+        // The first connection receives the session ticket with its first read.
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", early_port)));
+        let mut socket = p!(connector.connect_early("127.0.0.1", socket));
+        assert_eq!(p!(socket.write_early_data(b"early")), 5);
+        p!(socket.handshake());
+        p!(socket.write_all(b"world"));
+
+        assert!(socket.session_reused());
+        assert!(socket.early_data_accepted());
+
+        p!(j.join());
+        p!(early.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn read_early_data() {
+        let acceptor = p!(TlsAcceptor::builder(ip_identity()).set_max_early_data(1024).build());
+
+        let mut expected = vec![&b""[..], b"early"].into_iter();
+        let (port, j) = listen(2, move |socket| {
+            let mut socket = p!(acceptor.accept_early(socket));
+
+            let mut early = vec![];
+            let mut buf = [0; 1024];
+
+            loop {
+                match p!(socket.read_early_data(&mut buf)) {
+                    0 => break,
+                    len => early.extend_from_slice(&buf[..len]),
+                }
+            }
+
+            p!(socket.handshake());
+            assert_eq!(early, expected.next().unwrap());
+
+            let mut buf = [0; 5];
+            p!(socket.read_exact(&mut buf));
+            assert_eq!(&buf, b"hello");
+            p!(socket.write_all(b"world"));
+
+            // The session is dropped from the cache if the connection isn't shut down.
+            p!(socket.shutdown());
+        });
+
+        let connector = p!(ip_connector().enable_session_cache(true).build());
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+        p!(socket.write_all(b"hello"));
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(p!(socket.connection_info()).protocol, "TLSv1.3");
+
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect_early("127.0.0.1", socket));
+        p!(socket.write_early_data(b"early"));
+        p!(socket.write_all(b"hello"));
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"world");
+        assert!(socket.early_data_accepted());
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn peer_certificate_fingerprint() {
+        let (port, j) = serve_once(identity_acceptor(), move |socket| {
+            assert!(p!(socket.peer_certificate_fingerprint(MessageDigest::sha256())).is_none());
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let socket = p!(connector.connect("foobar.com", socket));
+
+        let cert_der = include_bytes!("../../tests/cert.der");
+        let fingerprint = p!(socket.peer_certificate_fingerprint(MessageDigest::sha256()));
+        assert_eq!(fingerprint, Some(openssl::sha::sha256(cert_der).to_vec()));
+
+        let cert = p!(Certificate::from_der(cert_der));
+        assert_eq!(fingerprint, Some(p!(cert.fingerprint(MessageDigest::sha256()))));
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connect_would_block() {
+        let listener = p!(TcpListener::bind("0.0.0.0:0"));
+        let port = p!(listener.local_addr()).port();
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        p!(socket.set_nonblocking(true));
+
+        let builder = p!(TlsConnector::new());
+        let err = builder.connect("foobar.com", socket).unwrap_err();
+        assert!(err.is_would_block());
+    }
+
+    #[test]
+    fn connect_nonblocking() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(b"world"));
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        p!(socket.set_nonblocking(true));
+
+        let builder = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let mut result = builder.connect("foobar.com", socket);
+
+        let mut socket = loop {
+            match result {
+                Ok(socket) => break socket,
+                Err(e) => {
+                    let mid = e.into_mid_handshake().expect("handshake failed");
+                    thread::sleep(std::time::Duration::from_millis(10));
+                    result = mid.handshake();
+                }
+            }
+        };
+
+        p!(socket.get_ref().set_nonblocking(false));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"world");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn verify_mode() {
+        use crate::SslVerifyMode;
+
+        let acceptor = ip_acceptor();
+
+        let (port, j) = serve_hello(acceptor, 2);
+
+        // The self-signed certificate isn't trusted.
+        let connector = p!(TlsConnector::builder().set_verify_mode(SslVerifyMode::PEER).build());
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let err = connector.connect("127.0.0.1", socket).unwrap_err();
+        assert_eq!(err.verify_error(), Some(crate::VerifyError::SelfSigned));
+
+        let connector = p!(TlsConnector::builder().set_verify_mode(SslVerifyMode::NONE).build());
+        let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+        let mut socket = p!(connector.connect("127.0.0.1", socket));
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        assert_eq!(&buf, b"hello");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn accept_nonblocking() {
+        let acceptor = identity_acceptor();
+
+        let (port, j) = listen_once(move |socket| {
+            p!(socket.set_nonblocking(true));
+
+            let mut result = acceptor.accept(socket);
+            let mut interrupted = false;
+
+            let mut socket = loop {
+                match result {
+                    Ok(socket) => break socket,
+                    Err(e) => {
+                        let mid = e.into_mid_handshake().expect("handshake failed");
+                        interrupted = true;
+                        thread::sleep(std::time::Duration::from_millis(10));
+                        result = mid.handshake();
+                    }
+                }
+            };
+            assert!(interrupted);
+
+            p!(socket.get_ref().set_nonblocking(false));
+            p!(socket.write_all(b"world"));
+        });
+
+        // Give the server the chance to run into the missing client hello.
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        thread::sleep(std::time::Duration::from_millis(50));
+
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let mut socket = p!(connector.connect("foobar.com", socket));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"world");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn connect_setup() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(b"world"));
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        p!(socket.set_nonblocking(true));
+
+        let connector = p!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let mut socket = p!(connector.setup("foobar.com", socket));
+        assert!(p!(socket.peer_certificate()).is_none());
+
+        loop {
+            match socket.handshake() {
+                Ok(()) => break,
+                Err(Error::Ssl(ref e, _)) if e.io_error().map(|e| e.kind()) == Some(std::io::ErrorKind::WouldBlock) => {
+                    thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(e) => panic!("handshake failed: {:?}", e),
+            }
+        }
+        assert!(p!(socket.peer_certificate()).is_some());
+
+        p!(socket.get_ref().set_nonblocking(false));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"world");
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn keylog_callback() {
+        use std::sync::{Arc, Mutex};
+
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(b"world"));
+        });
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let captured = lines.clone();
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let builder = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .set_keylog_callback(move |line| captured.lock().unwrap().push(line.to_string()))
+            .build());
+        let mut socket = p!(builder.connect("foobar.com", socket));
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"world");
+
+        let lines = lines.lock().unwrap();
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|line| !line.ends_with('\n')));
+
+        p!(j.join());
+    }
+
+    #[test]
+    #[cfg(have_ossl110)]
+    fn server_security_level_too_high() {
+        let identity = test_identity();
+        assert!(TlsAcceptor::builder(identity).set_security_level(5).build().is_err());
+    }
+
+    #[test]
+    #[cfg(have_ossl110)]
+    fn connect_security_level_too_high() {
+        let (port, j) = serve_hello(identity_acceptor(), 1);
+
+        let root_ca = include_bytes!("../../tests/root-ca.der");
+        let root_ca = Certificate::from_der(root_ca).unwrap();
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let builder = p!(TlsConnector::builder()
+            .add_root_certificate(root_ca)
+            .set_security_level(5)
+            .build());
+        assert!(builder.connect("foobar.com", socket).is_err());
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn ocsp_response_not_stapled() {
+        let (port, j) = serve_once(identity_acceptor(), move |mut socket| {
+            p!(socket.write_all(b"world"));
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let builder = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .set_status_request(true)
+            .build());
+        let mut socket = p!(builder.connect("foobar.com", socket));
+        assert!(p!(socket.ocsp_response()).is_none());
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"world");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn ocsp_response_stapled() {
+        let identity = test_identity();
+        let builder = p!(TlsAcceptor::builder(identity)
+            .set_ocsp_response(b"stapled".to_vec())
+            .build());
+
+        let (port, j) = serve_once(builder, move |mut socket| {
+            p!(socket.write_all(b"world"));
+        });
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let builder = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .set_status_request(true)
+            .build());
+        let mut socket = p!(builder.connect("foobar.com", socket));
+        assert_eq!(p!(socket.ocsp_response()).as_deref(), Some(&b"stapled"[..]));
+
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"world");
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn session_resumption() {
+        let (port, j) = serve(ip_acceptor(), 2, |mut socket| {
+            p!(socket.write_all(b"world"));
+            p!(socket.shutdown());
+        });
+
+        let connector = p!(ip_connector().enable_session_cache(true).build());
+
+        for &reused in &[false, true] {
+            let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+            let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+            let mut buf = vec![];
+            p!(socket.read_to_end(&mut buf));
+            assert_eq!(buf, b"world");
+            assert_eq!(socket.session_reused(), reused);
+            p!(socket.shutdown());
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn session_removed_after_unclean_shutdown() {
+        let (port, j) = serve(ip_acceptor(), 2, |mut socket| {
+            p!(socket.write_all(b"world"));
+            p!(socket.shutdown());
+        });
+
+        let connector = p!(ip_connector().enable_session_cache(true).build());
+
+        // OpenSSL invalidates the session of a connection dropped without a shutdown.
+        for _ in 0..2 {
+            let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+            let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+            let mut buf = vec![];
+            p!(socket.read_to_end(&mut buf));
+            assert!(!socket.session_reused());
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn session_cache_not_shared_with_clones() {
+        let (port, j) = serve(ip_acceptor(), 2, |mut socket| {
+            p!(socket.write_all(b"world"));
+            p!(socket.shutdown());
+        });
+
+        let mut builder = ip_connector();
+        builder.enable_session_cache(true);
+        let clone = p!(builder.clone().build());
+        let connector = p!(builder.build());
+
+        for connector in &[connector, clone] {
+            let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+            let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+            let mut buf = vec![];
+            p!(socket.read_to_end(&mut buf));
+            assert!(!socket.session_reused());
+        }
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn session_cache_skipped_when_verification_relaxed() {
+        // The certificate of the test identity is expired, so resuming a session of the lenient
+        // connector is the only way the strict one could connect.
+        let (port, j) = serve_hello(identity_acceptor(), 2);
+
+        let mut builder = TlsConnector::builder();
+        builder.enable_session_cache(true);
+        let strict = p!(builder.build());
+        let lenient = p!(builder.danger_accept_invalid_certs(true).build());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(lenient.connect("foobar.com", socket));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"hello");
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        assert!(strict.connect("foobar.com", socket).is_err());
+
+        p!(j.join());
+    }
+
+    /// A session store keeping the sessions in a map.
+    #[derive(Default)]
+    struct Store(Mutex<HashMap<String, Vec<u8>>>);
+
+    impl SessionStore for Store {
+        fn get(&self, key: &str) -> Option<Vec<u8>> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn put(&self, key: &str, session_der: Vec<u8>) {
+            self.0.lock().unwrap().insert(key.into(), session_der);
+        }
+    }
+
+    #[test]
+    fn session_store() {
+        let (port, j) = serve(ip_acceptor(), 2, |mut socket| {
+            p!(socket.write_all(b"world"));
+        });
+
+        let store = Arc::new(Store::default());
+
+        for &reused in &[false, true] {
+            // A new connector every time, sharing only the store.
+            let connector = p!(ip_connector().set_session_store(store.clone()).build());
+
+            let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+            let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+            let mut buf = vec![];
+            p!(socket.read_to_end(&mut buf));
+            assert_eq!(buf, b"world");
+            assert_eq!(socket.session_reused(), reused);
+        }
+
+        assert!(store.0.lock().unwrap().contains_key("127.0.0.1"));
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn session_store_shared_with_lenient_connector() {
+        // The certificate of the test identity is expired, so resuming a session of the lenient
+        // connector is the only way the strict one could connect.
+        let (port, j) = serve_hello(identity_acceptor(), 2);
+
+        let store = Arc::new(Store::default());
+        let lenient = p!(TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .set_session_store(store.clone())
+            .build());
+        let strict = p!(TlsConnector::builder().set_session_store(store.clone()).build());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        let mut socket = p!(lenient.connect("foobar.com", socket));
+        let mut buf = vec![];
+        p!(socket.read_to_end(&mut buf));
+        assert_eq!(buf, b"hello");
+        assert!(store.0.lock().unwrap().is_empty());
+
+        let socket = p!(TcpStream::connect(("localhost", port)));
+        assert!(strict.connect("foobar.com", socket).is_err());
+
+        p!(j.join());
+    }
+
+    #[cfg(have_ossl110)]
+    fn resume_across_acceptors(first: TlsAcceptor, second: TlsAcceptor) -> bool {
+        // Sessions are resumed by host, so the port doesn't matter.
+        let servers = vec![serve_hello(first, 1), serve_hello(second, 1)];
+
+        let connector = p!(ip_connector().enable_session_cache(true).build());
+        let mut reused = false;
+
+        for (port, j) in servers {
+            let socket = p!(TcpStream::connect(("127.0.0.1", port)));
+            let mut socket = p!(connector.connect("127.0.0.1", socket));
+
+            let mut buf = vec![];
+            p!(socket.read_to_end(&mut buf));
+            assert_eq!(buf, b"hello");
+            reused = socket.session_reused();
+            p!(socket.shutdown());
+            p!(j.join());
+        }
+
+        reused
+    }
+
+    #[test]
+    #[cfg(have_ossl110)]
+    fn session_ticket_key_shared() {
+        let identity = ip_identity();
+        let key = [7; 80];
+
+        let first = p!(TlsAcceptor::builder(identity.clone()).set_ticket_key(&key).build());
+        let second = p!(TlsAcceptor::builder(identity.clone()).set_ticket_key(&key).build());
+        assert!(resume_across_acceptors(first, second));
+
+        let first = p!(TlsAcceptor::new(identity.clone()));
+        let second = p!(TlsAcceptor::new(identity));
+        assert!(!resume_across_acceptors(first, second));
+    }
+
+    #[test]
+    #[cfg(have_ossl110)]
+    fn session_tickets_disabled() {
+        let identity = ip_identity();
+        let key = [7; 80];
+
+        let first = p!(TlsAcceptor::builder(identity.clone())
+            .set_ticket_key(&key)
+            .disable_tickets(true)
+            .build());
+        let second = p!(TlsAcceptor::builder(identity)
+            .set_ticket_key(&key)
+            .disable_tickets(true)
+            .build());
+        assert!(!resume_across_acceptors(first, second));
+    }
+
+    #[test]
+    fn session_cache_settings() {
+        use foreign_types::ForeignTypeRef;
+
+        extern "C" {
+            fn SSL_CTX_get_timeout(ctx: *const openssl_sys::SSL_CTX) -> std::os::raw::c_long;
+        }
+
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity)
+            .set_session_cache_size(128)
+            .set_session_timeout(60)
+            .build());
+
+        let context = acceptor.0.context();
+        assert_eq!(context.session_cache_size(), 128);
+        assert_eq!(unsafe { SSL_CTX_get_timeout(context.as_ptr()) }, 60);
+    }
+
+    #[test]
+    fn acceptor_with_openssl() {
+        let identity = test_identity();
+        let acceptor = p!(TlsAcceptor::builder(identity.clone())
+            .max_protocol_version(Some(Protocol::Tlsv12))
+            .with_openssl(|acceptor| acceptor.set_cipher_list("ECDHE-RSA-AES128-GCM-SHA256"))
+            .build());
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let mut socket = connect_untrusted(port);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        let cipher = p!(socket.connection_info()).cipher;
+        assert_eq!(cipher.as_deref(), Some("ECDHE-RSA-AES128-GCM-SHA256"));
+
+        p!(j.join());
+
+        // Errors of the callback fail the build.
+        let res = TlsAcceptor::builder(identity)
+            .with_openssl(|acceptor| acceptor.set_cipher_list("NOPE"))
+            .build();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn dh_params() {
+        let identity = test_identity();
+        assert!(TlsAcceptor::builder(identity.clone()).set_dh_params(b"NOPE").is_err());
+
+        let acceptor = p!(p!(TlsAcceptor::builder(identity)
+            .max_protocol_version(Some(Protocol::Tlsv12))
+            .with_openssl(|acceptor| acceptor.set_cipher_list("DHE-RSA-AES128-GCM-SHA256"))
+            .set_dh_params(include_bytes!("../../tests/dhparams.pem")))
+        .build());
+
+        let (port, j) = serve_once(acceptor, move |mut socket| {
+            p!(socket.write_all(b"hello"));
+        });
+
+        let mut socket = connect_untrusted(port);
+
+        let mut buf = [0; 5];
+        p!(socket.read_exact(&mut buf));
+        let cipher = p!(socket.connection_info()).cipher;
+        assert_eq!(cipher.as_deref(), Some("DHE-RSA-AES128-GCM-SHA256"));
+
+        p!(j.join());
+    }
+
+    #[test]
+    fn session_ticket_key_invalid_length() {
+        let identity = test_identity();
+        assert!(TlsAcceptor::builder(identity).set_ticket_key(&[7; 12]).build().is_err());
+    }
+
+    #[test]
+    #[cfg(have_ossl111)]
+    fn groups_list_invalid() {
+        let identity = test_identity();
+        assert!(TlsAcceptor::builder(identity).set_groups_list("NOPE").build().is_err());
+        assert!(TlsConnector::builder().set_groups_list("X25519:NOPE").build().is_err());
+    }
+
     #[test]
     fn server_untrusted_unverified() {
         let buf = include_bytes!("../../tests/identity.p12");
@@ -0,0 +1,79 @@
+use std::{
+    cmp, fmt, mem,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{self, Ordering},
+};
+
+/// A byte buffer for secrets, overwritten with zeros when dropped.
+///
+/// The buffer never reallocates in place: when it grows, the contents are copied to a
+/// new buffer and the old one is scrubbed, so no copies are left in freed memory.
+#[derive(Default)]
+pub(crate) struct Zeroizing(Vec<u8>);
+
+impl Zeroizing {
+    /// Appends `data` to the buffer.
+    pub(crate) fn extend_from_slice(&mut self, data: &[u8]) {
+        if self.0.capacity() - self.0.len() < data.len() {
+            let capacity = cmp::max(self.0.capacity() * 2, self.0.len() + data.len());
+            let mut grown = Vec::with_capacity(capacity);
+            grown.extend_from_slice(&self.0);
+
+            // Dropping the old buffer scrubs it.
+            drop(mem::replace(self, Zeroizing(grown)));
+        }
+
+        self.0.extend_from_slice(data);
+    }
+}
+
+impl Deref for Zeroizing {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for Zeroizing {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl fmt::Debug for Zeroizing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Zeroizing").finish()
+    }
+}
+
+impl Drop for Zeroizing {
+    fn drop(&mut self) {
+        let ptr = self.0.as_mut_ptr();
+
+        // Volatile writes over the whole allocation can't be optimized away, even though
+        // the buffer is freed right after.
+        for i in 0..self.0.capacity() {
+            unsafe { ptr::write_volatile(ptr.add(i), 0) };
+        }
+
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Zeroizing;
+
+    #[test]
+    fn extend_grows() {
+        let mut buf = Zeroizing::default();
+
+        for chunk in [&b"hello"[..], b" ", b"world"].iter() {
+            buf.extend_from_slice(chunk);
+        }
+
+        assert_eq!(&*buf, b"hello world");
+    }
+}
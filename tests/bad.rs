@@ -1,12 +1,8 @@
 // #![warn(rust_2018_idioms)]
 
 use async_std::net::TcpStream;
-use env_logger;
-use opentls::async_io::TlsConnector;
-use std::{
-    io::{self, Error},
-    net::ToSocketAddrs,
-};
+use opentls::{async_io::TlsConnector, Error, VerifyError};
+use std::net::ToSocketAddrs;
 
 macro_rules! t {
     ($e:expr) => {
@@ -24,10 +20,7 @@ async fn get_host(host: &'static str) -> Error {
 
     let socket = t!(TcpStream::connect(&addr).await);
     let cx = TlsConnector::new();
-    let res = cx
-        .connect(host, socket)
-        .await
-        .map_err(|e| Error::new(io::ErrorKind::Other, e));
+    let res = cx.connect(host, socket).await;
 
     assert!(res.is_err());
     res.err().unwrap()
@@ -36,23 +29,24 @@ async fn get_host(host: &'static str) -> Error {
 #[async_std::test]
 async fn expired() {
     let err = get_host("expired.badssl.com").await;
-    assert!(format!("{}", err).contains("certificate verify failed"))
+    assert_eq!(err.verify_error(), Some(VerifyError::Expired));
 }
 
 #[async_std::test]
 async fn wrong_host() {
     let err = get_host("wrong.host.badssl.com").await;
-    assert!(format!("{}", err).contains("certificate verify failed"))
+    assert_eq!(err.verify_error(), Some(VerifyError::HostnameMismatch));
 }
 
 #[async_std::test]
 async fn self_signed() {
     let err = get_host("self-signed.badssl.com").await;
-    assert!(format!("{}", err).contains("certificate verify failed"))
+    assert_eq!(err.verify_error(), Some(VerifyError::SelfSigned));
 }
 
 #[async_std::test]
 async fn untrusted_root() {
     let err = get_host("untrusted-root.badssl.com").await;
-    assert!(format!("{}", err).contains("certificate verify failed"))
+    // The server sends its untrusted root along with the chain.
+    assert_eq!(err.verify_error(), Some(VerifyError::SelfSigned));
 }
@@ -1,6 +1,6 @@
 #![warn(rust_2018_idioms)]
 
-use std::net::ToSocketAddrs;
+use std::{convert::TryFrom, net::ToSocketAddrs};
 
 use async_std::net::TcpStream;
 use async_std::prelude::*;
@@ -29,7 +29,8 @@ async fn fetch_google() {
     // of our request, then flushing, then finally read off the response.
     let connector = opentls::async_io::TlsConnector::new();
     let url = url::Url::parse("https://google.com/").unwrap();
-    let mut socket = t!(connector.connect(&url, socket).await);
+    let host = t!(opentls::Host::try_from(&url));
+    let mut socket = t!(connector.connect(host, socket).await);
     t!(socket.write_all(b"GET / HTTP/1.0\r\n\r\n").await);
     let mut data = Vec::new();
     t!(socket.read_to_end(&mut data).await);